@@ -1,8 +1,8 @@
-use std::path::Path;
-
 fn main() {
     #[cfg(windows)]
     {
+        use std::path::Path;
+
         // Set up the Windows resource
         let mut res = winres::WindowsResource::new();
 
@@ -0,0 +1,92 @@
+// This module contains the frame pacing shared by everything that animates (the theme blend and
+// the busy spinners), the hover easing for the title bar and submit buttons, and the "reduce
+// motion" switch: with it on, everything but the spinners completes instantly. Each animated frame asks for the next one at the user's chosen rate instead
+// of straight away, so a lower rate directly lowers CPU use; once nothing is animating, nothing
+// asks for repaints and egui sleeps until the next input.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use eframe::egui;
+
+const HOVER_SECONDS: f32 = 0.12;
+
+// Whether the OS asks apps to cut down on motion, checked once and used as the setting's
+// default. Where there's no such preference (or it can't be read) the answer is no.
+pub fn os_prefers_reduced_motion() -> bool {
+    static PREFERENCE: OnceLock<bool> = OnceLock::new();
+    *PREFERENCE.get_or_init(detect_reduced_motion)
+}
+
+#[cfg(windows)]
+fn detect_reduced_motion() -> bool {
+    use winapi::um::winuser::{SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION};
+
+    let mut animations: i32 = 1;
+    let read = unsafe { SystemParametersInfoW(SPI_GETCLIENTAREAANIMATION, 0, &mut animations as *mut i32 as *mut _, 0) };
+    read != 0 && animations == 0
+}
+
+#[cfg(target_os = "macos")]
+fn detect_reduced_motion() -> bool {
+    command_output("defaults", &["read", "com.apple.universalaccess", "reduceMotion"]).is_some_and(|out| out == "1")
+}
+
+// GNOME's switch, which most other desktops' settings apps also write
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect_reduced_motion() -> bool {
+    command_output("gsettings", &["get", "org.gnome.desktop.interface", "enable-animations"]).is_some_and(|out| out == "false")
+}
+
+#[cfg(unix)]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).stderr(std::process::Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// egui's own animations (collapsing headers, scrolling to a widget) follow the setting too
+pub fn apply_reduce_motion(ctx: &egui::Context, reduce_motion: bool) {
+    let defaults = egui::Style::default();
+    let animation_time = if reduce_motion { 0.0 } else { defaults.animation_time };
+    if ctx.style().animation_time != animation_time {
+        ctx.style_mut(|style| {
+            style.animation_time = animation_time;
+            style.scroll_animation = if reduce_motion { egui::style::ScrollAnimation::none() } else { defaults.scroll_animation };
+        });
+    }
+}
+
+// Ask for the next animation frame at `fps`
+pub fn request_frame(ctx: &egui::Context, fps: u32) {
+    ctx.request_repaint_after(Duration::from_secs_f32(1.0 / fps.max(1) as f32));
+}
+
+// How far into its hover look a button is, 0..=1. Eases in and out unless motion is reduced,
+// in which case it snaps like a plain egui hover.
+pub fn hover_amount(ctx: &egui::Context, id: egui::Id, hovered: bool, reduce_motion: bool) -> f32 {
+    if reduce_motion {
+        return if hovered { 1.0 } else { 0.0 };
+    }
+    ctx.animate_bool_with_time(id, hovered, HOVER_SECONDS)
+}
+
+// The same arc as egui's Spinner, which repaints as fast as the display allows
+pub fn spinner(ui: &mut egui::Ui, size: f32, fps: u32) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+    if ui.is_rect_visible(rect) {
+        request_frame(ui.ctx(), fps);
+        let radius = rect.height() / 2.0 - 2.0;
+        let time = ui.input(|i| i.time);
+        let start_angle = time * std::f64::consts::TAU;
+        let end_angle = start_angle + 240f64.to_radians() * time.sin();
+        let points: Vec<egui::Pos2> = (0..20)
+            .map(|i| {
+                let angle = egui::lerp(start_angle..=end_angle, i as f64 / 20.0);
+                let (sin, cos) = angle.sin_cos();
+                rect.center() + radius * egui::vec2(cos as f32, sin as f32)
+            })
+            .collect();
+        ui.painter().add(egui::Shape::line(points, egui::Stroke::new(3.0, ui.visuals().strong_text_color())));
+    }
+    response
+}
@@ -0,0 +1,97 @@
+// This module contains the opt-in audit log: one JSON line per card that went out, appended to a
+// local file as a durable record of what was sent where. Lines hold the masked number and the
+// expiry, never the full number, the CVV or server messages (which can echo the request). The
+// file is rotated to a single ".1" backup once it reaches its size cap.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::network::{self, SendConfig, SendError, SendSuccess};
+use crate::settings::{self, Settings};
+use crate::validation;
+use crate::CardInfo;
+
+const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    timestamp: String, // RFC 3339, local time with offset
+    masked_number: String,
+    expiry_date: &'a str,
+    endpoint: String, // Origin only, so no credentials or query strings
+    idempotency_key: Option<&'a str>,
+    result: &'a str,
+    status: Option<u16>,
+    transaction_id: Option<&'a str>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl AuditLog {
+    // None unless the log is turned on. The default location is in the config directory, so
+    // memory-only mode turns it off there; a configured path is still written.
+    pub fn from_settings(settings: &Settings) -> Option<Self> {
+        if !settings.audit_log_enabled {
+            return None;
+        }
+        let configured = settings.audit_log_path.trim();
+        let path = if configured.is_empty() {
+            if settings::memory_only() {
+                return None;
+            }
+            default_path()?
+        } else {
+            PathBuf::from(configured)
+        };
+        Some(Self { path, max_bytes: u64::from(settings.audit_log_max_kib) * 1024 })
+    }
+
+    // `result` is a short name such as "sent", "queued" or "failed"
+    pub fn record(&self, card_info: &CardInfo, config: &SendConfig, result: &str, outcome: Result<&SendSuccess, &SendError>) {
+        let record = AuditRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            masked_number: validation::masked_card_number(&card_info.card_number),
+            expiry_date: &card_info.expiry_date,
+            endpoint: network::endpoint_origin(config.destination()),
+            idempotency_key: config.idempotency_key.as_deref(),
+            result,
+            status: match outcome {
+                Ok(success) => success.status,
+                Err(e) => e.status,
+            },
+            transaction_id: outcome.ok().and_then(|success| success.transaction_id.as_deref()),
+        };
+        let mut line = serde_json::to_string(&record).unwrap_or_default();
+        line.push('\n');
+        if let Err(e) = self.append(&line) {
+            tracing::error!(path = %self.path.display(), "failed to write the audit log: {}", e);
+        }
+    }
+
+    fn append(&self, line: &str) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let size = std::fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            std::fs::rename(&self.path, backup_path(&self.path))?;
+        }
+        std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())
+    }
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(AUDIT_FILE_NAME))
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
@@ -0,0 +1,295 @@
+// This module contains batch mode: sending every card from a JSON file, one after another
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::audit::AuditLog;
+use crate::network::{self, SendConfig};
+use crate::patterns::CompiledPatterns;
+use crate::pipeline::{self, Submission, SubmitOutcome};
+use crate::queue::OfflineQueue;
+use crate::validation::{self, ExpiryFormat};
+use crate::{settings, CardInfo};
+
+const CHECKPOINT_FILE_NAME: &str = "batch_progress.json";
+const LATENCY_WINDOW: usize = 10; // Number of recent sends averaged for the ETA
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Written after every card so a crash can resume; only the source path, never card data
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchCheckpoint {
+    pub source: PathBuf,
+    pub completed: usize,
+}
+
+impl BatchCheckpoint {
+    pub fn load() -> Option<Self> {
+        settings::load_or_default(checkpoint_path())
+    }
+
+    fn save(&self) {
+        if settings::memory_only() {
+            return;
+        }
+        let Some(path) = checkpoint_path() else {
+            return;
+        };
+        let result = settings::save_atomic(&path, serde_json::to_string(self).unwrap_or_default().as_bytes());
+        if let Err(e) = result {
+            tracing::warn!("failed to save batch progress: {}", e);
+        }
+    }
+
+    pub fn clear() {
+        if let Some(path) = checkpoint_path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn checkpoint_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(CHECKPOINT_FILE_NAME))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryStatus {
+    Pending,
+    Earlier, // Sent before the batch was resumed from a checkpoint; the outcome wasn't kept
+    Sent(Option<String>), // Transaction id, if the server returned one
+    Queued(String), // No answer; handed to the offline queue, which retries it from now on
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct BatchProgress {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub queued: usize,
+    pub finished: bool,
+    pub last_error: Option<String>,
+    pub statuses: Vec<EntryStatus>, // One per card, updated in place as sends finish
+    recent_latencies: VecDeque<Duration>,
+}
+
+impl BatchProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f32 / self.total as f32
+        }
+    }
+
+    // Remaining cards times the rolling average latency; None until something has been timed
+    pub fn eta(&self) -> Option<Duration> {
+        if self.recent_latencies.is_empty() {
+            return None;
+        }
+        let average = self.recent_latencies.iter().sum::<Duration>() / self.recent_latencies.len() as u32;
+        Some(average * (self.total - self.completed) as u32)
+    }
+
+    pub fn failed_indices(&self) -> Vec<usize> {
+        self.statuses
+            .iter()
+            .enumerate()
+            .filter(|(_, status)| matches!(status, EntryStatus::Failed(_)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn record_latency(&mut self, latency: Duration) {
+        if self.recent_latencies.len() == LATENCY_WINDOW {
+            self.recent_latencies.pop_front();
+        }
+        self.recent_latencies.push_back(latency);
+    }
+}
+
+// A loaded card plus the idempotency key every attempt at sending it reuses; the card is wiped
+// once the run and any retries of it are gone
+struct Entry {
+    card_info: Zeroizing<CardInfo>,
+    idempotency_key: String,
+}
+
+// What every card is sent with besides the config, the same as a send from the form
+#[derive(Clone)]
+pub struct SendWith {
+    pub patterns: CompiledPatterns, // The settings' custom field patterns when the pass started
+    pub audit: Option<AuditLog>,
+    pub queue: Option<Arc<Mutex<OfflineQueue>>>, // None when queueing is off
+}
+
+// Handle the UI keeps while a batch runs in the background
+pub struct BatchRun {
+    pub source: PathBuf,
+    pub progress: Arc<Mutex<BatchProgress>>,
+    pub paused: Arc<AtomicBool>,
+    entries: Arc<Vec<Entry>>,
+}
+
+impl BatchRun {
+    // Resend only the cards that failed, with the same idempotency keys as their first attempt.
+    // Does nothing while the batch is still running.
+    pub fn retry_failed(&self, config: SendConfig, with: SendWith, ctx: egui::Context) {
+        let indices = {
+            let mut progress = self.progress.lock().unwrap();
+            if !progress.finished {
+                return;
+            }
+            let indices = progress.failed_indices();
+            for &index in &indices {
+                progress.statuses[index] = EntryStatus::Pending;
+            }
+            progress.completed -= indices.len();
+            progress.failed = 0;
+            progress.last_error = None;
+            progress.finished = false;
+            indices
+        };
+        let run = Pass {
+            source: None,
+            entries: self.entries.clone(),
+            progress: self.progress.clone(),
+            paused: self.paused.clone(),
+            with,
+        };
+        tokio::spawn(run.send(indices, config, ctx));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+pub fn load_entries(path: &Path) -> Result<Vec<CardInfo>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    let mut entries: Vec<CardInfo> =
+        serde_json::from_str(&contents).map_err(|e| format!("Expected a JSON array of cards: {}", e))?;
+    for entry in &mut entries {
+        validation::cap_card_info(entry);
+    }
+    Ok(entries)
+}
+
+// Start sending from `start_at` (non-zero when resuming from a checkpoint)
+pub fn start(
+    source: PathBuf,
+    start_at: usize,
+    config: SendConfig,
+    with: SendWith,
+    expiry_format: ExpiryFormat,
+    ctx: egui::Context,
+) -> Result<BatchRun, String> {
+    let entries: Vec<Entry> = load_entries(&source)?
+        .into_iter()
+        .map(|mut card_info| {
+            card_info.expiry_date = validation::normalize_expiry(&card_info.expiry_date, expiry_format);
+            Entry { card_info: Zeroizing::new(card_info), idempotency_key: network::new_idempotency_key() }
+        })
+        .collect();
+    let start_at = start_at.min(entries.len());
+
+    let mut statuses = vec![EntryStatus::Pending; entries.len()];
+    statuses[..start_at].fill(EntryStatus::Earlier);
+    let progress = Arc::new(Mutex::new(BatchProgress {
+        total: entries.len(),
+        completed: start_at,
+        statuses,
+        ..Default::default()
+    }));
+    let paused = Arc::new(AtomicBool::new(false));
+    let entries = Arc::new(entries);
+
+    let run = Pass {
+        source: Some(source.clone()),
+        entries: entries.clone(),
+        progress: progress.clone(),
+        paused: paused.clone(),
+        with,
+    };
+    tokio::spawn(run.send((start_at..entries.len()).collect(), config, ctx));
+
+    Ok(BatchRun { source, progress, paused, entries })
+}
+
+// One trip through some of the batch's cards: the whole file, or just the failures on a retry
+struct Pass {
+    source: Option<PathBuf>, // Where to checkpoint; retries aren't checkpointed
+    entries: Arc<Vec<Entry>>,
+    progress: Arc<Mutex<BatchProgress>>,
+    paused: Arc<AtomicBool>,
+    with: SendWith,
+}
+
+impl Pass {
+    async fn send(self, indices: Vec<usize>, config: SendConfig, ctx: egui::Context) {
+        for index in indices {
+            while self.paused.load(Ordering::Relaxed) {
+                tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+            }
+
+            // Hard validation failures are counted as failed without touching the network; there's
+            // nobody to accept warnings, and starting the batch was the confirmation
+            let entry = &self.entries[index];
+            let started = Instant::now();
+            let config = SendConfig { idempotency_key: Some(entry.idempotency_key.clone()), ..config.clone() };
+            let submission = Submission {
+                card_info: &entry.card_info,
+                config: &config,
+                patterns: &self.with.patterns,
+                allow_warnings: true,
+                confirmed: true,
+                queue: self.with.queue.as_deref(),
+                audit: self.with.audit.as_ref(),
+            };
+            let status = match pipeline::submit(&submission, &|_| {}).await {
+                SubmitOutcome::Sent(success) => EntryStatus::Sent(success.transaction_id),
+                SubmitOutcome::Queued(e) => EntryStatus::Queued(e.to_string()),
+                SubmitOutcome::Failed(e) => EntryStatus::Failed(e.to_string()),
+                SubmitOutcome::Rejected(validation) | SubmitOutcome::Validated(validation, _) => {
+                    EntryStatus::Failed(validation.error_summary())
+                }
+            };
+
+            {
+                let mut progress = self.progress.lock().unwrap();
+                progress.completed += 1;
+                progress.record_latency(started.elapsed());
+                match &status {
+                    EntryStatus::Queued(_) => progress.queued += 1,
+                    EntryStatus::Failed(e) => {
+                        progress.failed += 1;
+                        progress.last_error = Some(format!("Card {}: {}", index + 1, e));
+                    }
+                    _ => {}
+                }
+                progress.statuses[index] = status;
+            }
+
+            if let Some(source) = &self.source {
+                BatchCheckpoint { source: source.clone(), completed: index + 1 }.save();
+            }
+            ctx.request_repaint();
+        }
+
+        self.progress.lock().unwrap().finished = true;
+        if self.source.is_some() {
+            BatchCheckpoint::clear();
+        }
+        ctx.request_repaint();
+    }
+}
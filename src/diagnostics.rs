@@ -0,0 +1,176 @@
+// This module contains the developer diagnostics window: frame rate, frame time, what asked for
+// the last repaint and roughly how much memory the UI holds. Handy for checking that animations
+// settle down when nothing is moving. Nothing is measured while the window is closed.
+//
+// It also builds the plain-text report copied for bug reports, which never includes card data:
+// the only card detail it touches is the history's already-masked number.
+
+use std::fmt::Write;
+
+use eframe::egui;
+
+use crate::history::History;
+use crate::network::{self, Transport};
+use crate::settings::Settings;
+
+const EGUI_VERSION: &str = "0.31"; // Keep in step with Cargo.toml; egui doesn't expose its version
+const REPORT_HISTORY_ENTRIES: usize = 10;
+const FRAME_HISTORY_LENGTH: usize = 300;
+const FRAME_HISTORY_SECONDS: f32 = 2.0;
+
+pub struct Diagnostics {
+    pub open: bool,
+    frame_times: egui::util::History<f32>, // CPU seconds spent on each frame
+    frames: u64, // Painted since the window was opened
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            frame_times: egui::util::History::new(0..FRAME_HISTORY_LENGTH, FRAME_HISTORY_SECONDS),
+            frames: 0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.frame_times.clear();
+        self.frames = 0;
+    }
+
+    // Called once per frame; frame.info() describes the previous frame
+    pub fn record(&mut self, ctx: &egui::Context, frame: &eframe::Frame) {
+        if !self.open {
+            return;
+        }
+        self.frames += 1;
+        if let Some(cpu_usage) = frame.info().cpu_usage {
+            self.frame_times.add(ctx.input(|i| i.time), cpu_usage);
+        }
+    }
+
+    // Returns true when "Copy diagnostics" was clicked
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        if !self.open {
+            return false;
+        }
+        let mut open = self.open;
+        let mut copy = false;
+        egui::Window::new("Diagnostics")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("diagnostics_grid").num_columns(2).show(ui, |ui| {
+                    // Frames only arrive when something repaints, so an idle UI reads close to 0 fps
+                    ui.label("Frame rate:");
+                    ui.label(self.frame_times.rate().map_or("–".to_string(), |fps| format!("{:.1} fps", fps)));
+                    ui.end_row();
+                    ui.label("Frame time:");
+                    ui.label(match (self.frame_times.average(), self.frame_times.latest()) {
+                        (Some(average), Some(latest)) => format!("{:.2} ms avg, {:.2} ms last", average * 1e3, latest * 1e3),
+                        _ => "–".to_string(),
+                    });
+                    ui.end_row();
+                    ui.label("Frames painted:");
+                    ui.label(self.frames.to_string());
+                    ui.end_row();
+                    ui.label("Textures:");
+                    let (count, bytes) = {
+                        let textures = ctx.tex_manager();
+                        let textures = textures.read();
+                        (textures.num_allocated(), textures.allocated().map(|(_, meta)| meta.bytes_used()).sum::<usize>())
+                    };
+                    ui.label(format!("{} ({})", count, format_bytes(bytes)));
+                    ui.end_row();
+                    ui.label("Process memory:");
+                    ui.label(resident_memory().map_or("unavailable".to_string(), format_bytes));
+                    ui.end_row();
+                });
+                ui.separator();
+                ui.label("Last repaint requested by:");
+                let causes = ctx.repaint_causes();
+                if causes.is_empty() {
+                    ui.label(egui::RichText::new("input only").weak());
+                }
+                for cause in causes {
+                    ui.label(egui::RichText::new(cause.to_string()).monospace().size(11.0));
+                }
+                ui.separator();
+                copy = ui
+                    .button("Copy diagnostics")
+                    .on_hover_text("Environment details and recent outcomes for a bug report; no card data")
+                    .clicked();
+            });
+        if !open {
+            self.toggle();
+        }
+        // Keep the numbers moving while the window is up; this is the one repaint it causes
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        copy
+    }
+}
+
+pub fn report(settings: &Settings, history: &History) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "creditcard {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "OS: {} ({}, {})", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::FAMILY);
+    let _ = writeln!(report, "egui/eframe: {}", EGUI_VERSION);
+    let _ = writeln!(report, "Transport: {}", settings.transport.label());
+    let _ = match settings.transport {
+        Transport::Http => writeln!(
+            report,
+            "Endpoint: {} ({} {})",
+            network::endpoint_origin(&settings.endpoint),
+            settings.http_method,
+            settings.body_format.label()
+        ),
+        Transport::WebSocket => writeln!(report, "Endpoint: {}", network::endpoint_origin(&settings.websocket_url)),
+    };
+    let _ = writeln!(report, "Proxy: {}", if settings.proxy_url.trim().is_empty() { "none" } else { "configured" });
+    let _ = writeln!(
+        report,
+        "Timeouts: {} s connect, {} s total; {} retries within {} s",
+        settings.connect_timeout_seconds, settings.request_timeout_seconds, settings.max_retries, settings.retry_budget_seconds
+    );
+
+    let _ = writeln!(report, "\nRecent submissions (newest first):");
+    if history.is_empty() {
+        let _ = writeln!(report, "  none recorded");
+    }
+    for entry in history.iter_newest().take(REPORT_HISTORY_ENTRIES) {
+        let outcome = match &entry.outcome {
+            Ok(_) => "sent".to_string(),
+            Err(e) => format!("failed: {}", e),
+        };
+        let _ = writeln!(
+            report,
+            "  {} {} to {}: {}",
+            entry.submitted_at.format("%Y-%m-%d %H:%M:%S"),
+            entry.masked_number,
+            network::endpoint_origin(&entry.endpoint),
+            outcome
+        );
+    }
+    report
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1 << 20 {
+        format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64)
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+// Resident set size, from /proc on Linux; other platforms would need a native call
+fn resident_memory() -> Option<usize> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096) // The page size on every mainstream Linux target
+}
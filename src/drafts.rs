@@ -0,0 +1,135 @@
+// This module contains draft persistence: the half-filled form survives a restart, encrypted
+// at rest with a random key kept in the OS keyring. If there's no keyring nothing is written,
+// since the alternative would be card numbers in plaintext. The CVV is never part of a draft.
+
+use std::path::PathBuf;
+
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::settings;
+
+const DRAFT_FILE_NAME: &str = "draft.bin"; // Nonce followed by the sealed JSON
+const KEYRING_SERVICE: &str = "creditcard";
+const KEYRING_USER: &str = "draft-encryption-key";
+const KEY_LEN: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Draft {
+    pub card_number: String,
+    pub cardholder_name: String,
+    pub expiry_date: String,
+}
+
+impl Draft {
+    pub fn is_empty(&self) -> bool {
+        self.card_number.is_empty() && self.cardholder_name.is_empty() && self.expiry_date.is_empty()
+    }
+}
+
+impl Drop for Draft {
+    fn drop(&mut self) {
+        self.card_number.zeroize();
+        self.cardholder_name.zeroize();
+        self.expiry_date.zeroize();
+    }
+}
+
+pub fn key_store_available() -> bool {
+    keyring::Entry::store_status().is_ok()
+}
+
+// The draft key, created on first use when `create` is set
+fn draft_key(create: bool) -> Result<LessSafeKey, String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .map_err(|e| format!("No secure key store available ({}); drafts are not saved", e))?;
+    let mut secret = match entry.get_secret() {
+        Ok(secret) => secret,
+        Err(keyring::Error::NoEntry) if create => {
+            let mut secret = vec![0; KEY_LEN];
+            SystemRandom::new()
+                .fill(&mut secret)
+                .map_err(|_| "Couldn't generate a draft key".to_string())?;
+            entry
+                .set_secret(&secret)
+                .map_err(|e| format!("Couldn't store the draft key ({}); drafts are not saved", e))?;
+            secret
+        }
+        Err(e) => return Err(format!("Couldn't read the draft key: {}", e)),
+    };
+    let key = UnboundKey::new(&aead::CHACHA20_POLY1305, &secret).map_err(|_| "Draft key is corrupt".to_string());
+    secret.zeroize();
+    Ok(LessSafeKey::new(key?))
+}
+
+// Encrypt and write the draft; an empty draft just removes the file
+pub fn save(draft: &Draft) -> Result<(), String> {
+    if draft.is_empty() {
+        discard();
+        return Ok(());
+    }
+    if settings::memory_only() {
+        return Ok(());
+    }
+    let path = draft_path().ok_or_else(|| "No config directory available".to_string())?;
+    let key = draft_key(true)?;
+
+    let mut nonce = [0; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "Couldn't generate a nonce".to_string())?;
+    let mut sealed = serde_json::to_vec(draft).map_err(|e| e.to_string())?;
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+        .map_err(|_| "Couldn't encrypt the draft".to_string())?;
+
+    let mut contents = nonce.to_vec();
+    contents.extend_from_slice(&sealed);
+    settings::save_atomic(&path, &contents).map_err(|e| format!("Couldn't write the draft: {}", e))
+}
+
+// A draft that's cut short or won't decrypt is set aside as draft.bin.bak rather than reported
+// on every start; a missing key store is reported and the draft left alone.
+pub fn load() -> Result<Option<Draft>, String> {
+    let Some(path) = draft_path() else {
+        return Ok(None);
+    };
+    let Ok(contents) = std::fs::read(&path) else {
+        return Ok(None);
+    };
+    if contents.len() <= NONCE_LEN {
+        settings::set_aside_corrupt(&path, "too short");
+        return Ok(None);
+    }
+    let key = draft_key(false)?;
+
+    let (nonce, sealed) = contents.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Saved draft is corrupt".to_string())?;
+    let mut sealed = sealed.to_vec();
+    let result = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| "Saved draft couldn't be decrypted".to_string())
+        .and_then(|plaintext| serde_json::from_slice(plaintext).map_err(|e| format!("Saved draft is corrupt: {}", e)));
+    sealed.zeroize();
+    match result {
+        Ok(draft) => Ok(Some(draft)),
+        Err(e) => {
+            settings::set_aside_corrupt(&path, &e);
+            Ok(None)
+        }
+    }
+}
+
+// The set-aside copy and any write cut short go too: they still hold an (encrypted) card number
+pub fn discard() {
+    if let Some(path) = draft_path() {
+        let _ = std::fs::remove_file(settings::backup_path(&path));
+        let _ = std::fs::remove_file(settings::temp_path(&path));
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn draft_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(DRAFT_FILE_NAME))
+}
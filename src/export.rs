@@ -0,0 +1,107 @@
+// This module contains exporting the submission history to JSON or CSV files, and the request
+// signing public key as text. Exports go straight into the configured directory, never
+// overwriting an earlier file.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::history::HistoryEntry;
+use crate::settings;
+
+const EXPORT_FILE_STEM: &str = "export";
+const PUBLIC_KEY_FILE_STEM: &str = "signing-public-key";
+const EXPORT_DIR_NAME: &str = "exports"; // Under the config directory when none is configured
+const MAX_NUMBERED_FILES: u32 = 10_000; // Numbered names tried before giving up
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Json, ExportFormat::Csv];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+// Write `contents` to `export.json` if it's free, otherwise the first free `export-1.json`,
+// `export-2.json`, ... Each name is claimed with create_new, so a file that appears in the
+// meantime is never overwritten. Returns the path actually used.
+pub fn write_new_file(dir: &Path, stem: &str, extension: &str, contents: &[u8]) -> Result<PathBuf, String> {
+    for n in 0..MAX_NUMBERED_FILES {
+        let path = match n {
+            0 => dir.join(format!("{}.{}", stem, extension)),
+            n => dir.join(format!("{}-{}.{}", stem, n, extension)),
+        };
+        let mut file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Couldn't create {}: {}", path.display(), e)),
+        };
+        file.write_all(contents).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+        return Ok(path);
+    }
+    Err(format!("No free file name for {} in {}", stem, dir.display()))
+}
+
+pub fn export_dir(configured: &str) -> Option<PathBuf> {
+    let configured = configured.trim();
+    if configured.is_empty() {
+        settings::config_dir().map(|dir| dir.join(EXPORT_DIR_NAME))
+    } else {
+        Some(PathBuf::from(configured))
+    }
+}
+
+// Write the entries (oldest first) and return the path actually used
+pub fn export_history(entries: &[HistoryEntry], format: ExportFormat, configured_dir: &str) -> Result<PathBuf, String> {
+    let dir = export_dir(configured_dir).ok_or_else(|| "No export directory available".to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+
+    let contents = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(entries).map_err(|e| format!("Couldn't encode history: {}", e))?,
+        ExportFormat::Csv => history_csv(entries),
+    };
+    write_new_file(&dir, EXPORT_FILE_STEM, format.extension(), contents.as_bytes())
+}
+
+// The request-signing public key as one base64 line, for handing to the server's operators
+pub fn export_public_key(public_key: &str, configured_dir: &str) -> Result<PathBuf, String> {
+    let dir = export_dir(configured_dir).ok_or_else(|| "No export directory available".to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+    write_new_file(&dir, PUBLIC_KEY_FILE_STEM, "txt", format!("{}\n", public_key).as_bytes())
+}
+
+fn history_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from("submitted_at,masked_number,endpoint,result,detail\n");
+    for entry in entries {
+        let (result, detail) = match &entry.outcome {
+            Ok(transaction_id) => ("ok", transaction_id.clone().unwrap_or_default()),
+            Err(e) => ("error", e.clone()),
+        };
+        let row = [
+            entry.submitted_at.to_rfc3339(),
+            entry.masked_number.clone(),
+            entry.endpoint.clone(),
+            result.to_string(),
+            detail,
+        ];
+        csv.push_str(&row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,149 @@
+// This module contains headless mode: `--send <card.json>` (or `--send -` to read stdin) submits
+// one card with the saved settings and no window, for scripts.
+//
+// Exit codes are a contract; don't renumber them:
+//   0  success
+//   1  usage or input error (missing argument, unreadable or malformed card file)
+//   2  validation failure (nothing was sent)
+//   3  network error (couldn't connect, connection dropped, WebSocket rejection)
+//   4  server answered 4xx
+//   5  server answered 5xx
+//   6  timed out
+// Alongside the code, exactly one line is printed to stdout:
+//   result=<name> code=<n> status=<http status or -> transaction_id=<id or -> ms=<n> message=<JSON string>
+
+use std::io::Read;
+use std::time::Instant;
+
+use crate::audit::AuditLog;
+use crate::network::{self, SendConfig, SendError};
+use crate::patterns::CompiledPatterns;
+use crate::pipeline::{self, Submission, SubmitOutcome};
+use crate::settings::Settings;
+use crate::{import, validation, CardInfo};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Success = 0,
+    UsageError = 1,
+    ValidationFailure = 2,
+    NetworkError = 3,
+    ClientError = 4,
+    ServerError = 5,
+    Timeout = 6,
+}
+
+impl Outcome {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::UsageError => "usage_error",
+            Outcome::ValidationFailure => "validation_failure",
+            Outcome::NetworkError => "network_error",
+            Outcome::ClientError => "client_error",
+            Outcome::ServerError => "server_error",
+            Outcome::Timeout => "timeout",
+        }
+    }
+
+    pub fn from_send_error(error: &SendError) -> Self {
+        match error.status {
+            _ if error.timed_out => Outcome::Timeout,
+            Some(400..=499) => Outcome::ClientError,
+            Some(500..=599) => Outcome::ServerError,
+            _ => Outcome::NetworkError,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    status: Option<u16>,
+    transaction_id: Option<String>,
+    elapsed_ms: u128,
+    message: String,
+}
+
+// `source` is the argument after --send. Returns the exit code.
+pub async fn run(source: Option<&str>) -> i32 {
+    let (outcome, summary) = send(source).await;
+    println!(
+        "result={} code={} status={} transaction_id={} ms={} message={}",
+        outcome.name(),
+        outcome.code(),
+        summary.status.map_or_else(|| "-".to_string(), |status| status.to_string()),
+        summary.transaction_id.as_deref().unwrap_or("-"),
+        summary.elapsed_ms,
+        serde_json::to_string(&summary.message).unwrap_or_default()
+    );
+    outcome.code()
+}
+
+async fn send(source: Option<&str>) -> (Outcome, Summary) {
+    let failed = |outcome, message: String| (outcome, Summary { message, ..Default::default() });
+
+    let card_info = match read_card(source) {
+        Ok(card_info) => card_info,
+        Err(e) => return failed(Outcome::UsageError, e),
+    };
+
+    let settings = Settings::load();
+    let card_info = CardInfo {
+        expiry_date: validation::normalize_expiry(&card_info.expiry_date, settings.expiry_format),
+        ..card_info
+    };
+    let config = SendConfig { idempotency_key: Some(network::new_idempotency_key()), ..SendConfig::from_settings(&settings) };
+    let audit = AuditLog::from_settings(&settings);
+    // Warnings can't be confirmed without a window, so only hard errors stop a headless send
+    let submission = Submission {
+        card_info: &card_info,
+        config: &config,
+        patterns: &CompiledPatterns::compile(&settings.field_patterns),
+        allow_warnings: true,
+        confirmed: true,
+        queue: None,
+        audit: audit.as_ref(),
+    };
+    let started = Instant::now();
+    let outcome = pipeline::submit(&submission, &|_| {}).await;
+    let elapsed_ms = started.elapsed().as_millis();
+
+    match outcome {
+        SubmitOutcome::Rejected(validation) | SubmitOutcome::Validated(validation, _) => {
+            failed(Outcome::ValidationFailure, validation.error_summary())
+        }
+        SubmitOutcome::Sent(success) => (
+            Outcome::Success,
+            Summary {
+                status: success.status,
+                transaction_id: success.transaction_id,
+                elapsed_ms,
+                message: format!("Sent card {}", validation::masked_card_number(&card_info.card_number)),
+            },
+        ),
+        SubmitOutcome::Queued(e) | SubmitOutcome::Failed(e) => (
+            Outcome::from_send_error(&e),
+            Summary { status: e.status, elapsed_ms, message: e.message, ..Default::default() },
+        ),
+    }
+}
+
+fn read_card(source: Option<&str>) -> Result<CardInfo, String> {
+    let mut card_info = match source {
+        None => return Err("Usage: --send <card.json>, or --send - to read the card from stdin".to_string()),
+        Some("-") => {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .map_err(|e| format!("Couldn't read stdin: {}", e))?;
+            import::parse_card_json(&contents)?
+        }
+        Some(path) => import::load_card_file(std::path::Path::new(path))?,
+    };
+    validation::cap_card_info(&mut card_info);
+    Ok(card_info)
+}
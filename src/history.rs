@@ -0,0 +1,107 @@
+// This module contains the log of form submissions shown in the history panel.
+// Entries only ever hold the masked card number, so saving them to disk is opt-in but safe.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings;
+
+const HISTORY_FILE_NAME: &str = "history.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub submitted_at: chrono::DateTime<chrono::Local>,
+    pub masked_number: String,
+    pub endpoint: String,
+    pub outcome: Result<Option<String>, String>, // Transaction id on success, error text on failure
+}
+
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    limit: usize, // 0 keeps nothing
+    persist: bool,
+}
+
+impl History {
+    // Start from the saved history when persistence is on, trimmed to the current limit
+    pub fn load(limit: usize, persist: bool) -> Self {
+        let entries = if persist {
+            settings::load_or_default(history_path())
+        } else {
+            VecDeque::new()
+        };
+        let mut history = Self { entries, limit, persist };
+        history.trim();
+        history
+    }
+
+    // Apply changed settings: shrinking the limit drops the oldest entries straight away, and
+    // turning persistence off removes the saved file
+    pub fn configure(&mut self, limit: usize, persist: bool) {
+        if limit == self.limit && persist == self.persist {
+            return;
+        }
+        self.limit = limit;
+        self.persist = persist;
+        self.trim();
+        if persist {
+            self.save();
+        } else if let Some(path) = history_path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push_back(entry);
+        self.trim();
+        self.save();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Newest first, the order the panel lists them in
+    pub fn iter_newest(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().rev()
+    }
+
+    // Oldest first, for exporting
+    pub fn to_vec(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.save();
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.limit {
+            self.entries.pop_front();
+        }
+    }
+
+    fn save(&self) {
+        if !self.persist || settings::memory_only() {
+            return;
+        }
+        let Some(path) = history_path() else {
+            return;
+        };
+        let result = settings::save_atomic(&path, serde_json::to_string(&self.entries).unwrap_or_default().as_bytes());
+        if let Err(e) = result {
+            tracing::warn!("failed to save history: {}", e);
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(HISTORY_FILE_NAME))
+}
@@ -0,0 +1,29 @@
+// This module tracks input method (IME) composition. While a CJK or other IME is composing, the
+// focused text box holds provisional pre-edit text that the IME will replace on commit, so
+// anything that rewrites field contents has to wait until composition ends.
+
+use eframe::egui;
+
+#[derive(Default)]
+pub struct Composition {
+    active: bool,
+}
+
+impl Composition {
+    // Feed this frame's events
+    pub fn update(&mut self, events: &[egui::Event]) {
+        for event in events {
+            if let egui::Event::Ime(ime) = event {
+                self.active = match ime {
+                    egui::ImeEvent::Preedit(text) => !text.is_empty(), // An empty pre-edit means it was cancelled
+                    egui::ImeEvent::Enabled => self.active,
+                    egui::ImeEvent::Commit(_) | egui::ImeEvent::Disabled => false,
+                };
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
@@ -0,0 +1,90 @@
+// This module contains reading card data from outside the form: JSON files checked against the
+// embedded schema, and card details pasted as a single piece of text
+
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::{resources, validation, CardInfo};
+
+const MAX_REPORTED_SCHEMA_ERRORS: usize = 5;
+
+fn card_info_validator() -> &'static jsonschema::Validator {
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        let schema: serde_json::Value =
+            serde_json::from_str(resources::CARD_INFO_SCHEMA).expect("embedded card schema is valid JSON");
+        jsonschema::validator_for(&schema).expect("embedded card schema is a valid JSON Schema")
+    })
+}
+
+// Parse a single card, reporting schema violations (missing/extra fields, wrong types) precisely
+pub fn parse_card_json(contents: &str) -> Result<CardInfo, String> {
+    let instance: serde_json::Value = serde_json::from_str(contents).map_err(|e| format!("Not valid JSON: {}", e))?;
+
+    let errors: Vec<String> = card_info_validator()
+        .iter_errors(&instance)
+        .map(|error| {
+            let path = error.instance_path().to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+    if !errors.is_empty() {
+        let shown: Vec<&str> = errors.iter().take(MAX_REPORTED_SCHEMA_ERRORS).map(String::as_str).collect();
+        let more = errors.len().saturating_sub(MAX_REPORTED_SCHEMA_ERRORS);
+        let suffix = if more > 0 { format!(" (+{} more)", more) } else { String::new() };
+        return Err(format!("File doesn't match the card schema: {}{}", shown.join("; "), suffix));
+    }
+
+    serde_json::from_value(instance).map_err(|e| format!("Couldn't read card data: {}", e))
+}
+
+pub fn load_card_file(path: &Path) -> Result<CardInfo, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    parse_card_json(&contents)
+}
+
+// Smart paste: pull a whole card out of text like "4111 1111 1111 1111 12/27 123 Jane Doe" or
+// "4111111111111111|12/27|123". Only a complete card counts: a number passing the checksum, an
+// expiry and a CVV; a name is optional.
+pub fn parse_card_text(text: &str) -> Option<CardInfo> {
+    if let Ok(card_info) = parse_card_json(text) {
+        return Some(card_info);
+    }
+
+    let tokens: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || matches!(c, '|' | ',' | ';'))
+        .filter(|token| !token.is_empty())
+        .collect();
+    let expiry_index = tokens.iter().position(|token| validation::parse_expiry(token).is_some())?;
+
+    let mut numeric = Vec::new();
+    let mut name = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if index == expiry_index {
+            continue;
+        }
+        let digits = validation::card_digits(token);
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+            numeric.push(digits);
+        } else if token.chars().any(char::is_alphabetic) {
+            name.push(*token);
+        }
+    }
+
+    // Everything numeric but the last group is the card number; the last group is the CVV
+    let (security_code, number_groups) = numeric.split_last()?;
+    let card_number = number_groups.concat();
+    if !(3..=4).contains(&security_code.len()) || validation::check_card_number(&card_number).is_some() {
+        return None;
+    }
+    Some(CardInfo {
+        card_number,
+        cardholder_name: name.join(" "),
+        expiry_date: tokens[expiry_index].to_string(),
+        security_code: security_code.clone(),
+    })
+}
@@ -0,0 +1,178 @@
+// This module contains the remappable keyboard shortcuts
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Submit,
+    ToggleSettings, // Same as the title bar's gear button
+    ClearForm,
+    ClearField, // Only the focused field
+    PanicClose, // Wipe the card data and close immediately, no questions asked
+    ToggleDiagnostics, // Developer window with frame and repaint stats
+    ScreenshotMask, // Held rather than pressed: masks the form for a safe screenshot
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::Submit,
+        Action::ToggleSettings,
+        Action::ClearForm,
+        Action::ClearField,
+        Action::PanicClose,
+        Action::ToggleDiagnostics,
+        Action::ScreenshotMask,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Submit => "Submit",
+            Action::ToggleSettings => "Open/close settings",
+            Action::ClearForm => "Clear form",
+            Action::ClearField => "Clear current field",
+            Action::PanicClose => "Panic close",
+            Action::ToggleDiagnostics => "Toggle diagnostics",
+            Action::ScreenshotMask => "Mask for screenshot (hold)",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyBinding {
+    pub key: egui::Key,
+    pub command: bool, // Ctrl on Windows/Linux, Cmd on macOS
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    const fn command(key: egui::Key) -> Self {
+        Self { key, command: true, shift: false, alt: false }
+    }
+
+    pub fn shortcut(self) -> egui::KeyboardShortcut {
+        let mut modifiers = egui::Modifiers::NONE;
+        if self.command {
+            modifiers |= egui::Modifiers::COMMAND;
+        }
+        if self.shift {
+            modifiers |= egui::Modifiers::SHIFT;
+        }
+        if self.alt {
+            modifiers |= egui::Modifiers::ALT;
+        }
+        egui::KeyboardShortcut::new(modifiers, self.key)
+    }
+
+    // Whether the key and its modifiers are down right now, for bindings that act while held
+    pub fn is_held(self, ctx: &egui::Context) -> bool {
+        let shortcut = self.shortcut();
+        ctx.input(|input| input.key_down(shortcut.logical_key) && input.modifiers.matches_logically(shortcut.modifiers))
+    }
+
+    fn modifier_count(self) -> usize {
+        [self.command, self.shift, self.alt].into_iter().filter(|m| *m).count()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub submit: KeyBinding,
+    pub toggle_settings: KeyBinding,
+    pub clear_form: KeyBinding,
+    pub clear_field: KeyBinding,
+    pub panic_close: KeyBinding,
+    pub toggle_diagnostics: KeyBinding,
+    pub screenshot_mask: KeyBinding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            submit: KeyBinding::command(egui::Key::Enter),
+            toggle_settings: KeyBinding::command(egui::Key::Comma), // Types nothing, so it's safe while a field has focus
+            clear_form: KeyBinding::command(egui::Key::R),
+            clear_field: KeyBinding::command(egui::Key::Backspace), // Takes over the text box's delete-word
+            panic_close: KeyBinding { shift: true, ..KeyBinding::command(egui::Key::Q) },
+            toggle_diagnostics: KeyBinding { shift: true, ..KeyBinding::command(egui::Key::D) },
+            screenshot_mask: KeyBinding { command: false, ..KeyBinding::command(egui::Key::F9) }, // Easy to hold while pressing Print Screen
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn get(&self, action: Action) -> KeyBinding {
+        match action {
+            Action::Submit => self.submit,
+            Action::ToggleSettings => self.toggle_settings,
+            Action::ClearForm => self.clear_form,
+            Action::ClearField => self.clear_field,
+            Action::PanicClose => self.panic_close,
+            Action::ToggleDiagnostics => self.toggle_diagnostics,
+            Action::ScreenshotMask => self.screenshot_mask,
+        }
+    }
+
+    pub fn set(&mut self, action: Action, binding: KeyBinding) {
+        match action {
+            Action::Submit => self.submit = binding,
+            Action::ToggleSettings => self.toggle_settings = binding,
+            Action::ClearForm => self.clear_form = binding,
+            Action::ClearField => self.clear_field = binding,
+            Action::PanicClose => self.panic_close = binding,
+            Action::ToggleDiagnostics => self.toggle_diagnostics = binding,
+            Action::ScreenshotMask => self.screenshot_mask = binding,
+        }
+    }
+
+    // Other actions bound to the same keys as `action`
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let binding = self.get(action);
+        Action::ALL
+            .into_iter()
+            .filter(|other| *other != action && self.get(*other) == binding)
+            .collect()
+    }
+
+    // Consume this frame's shortcut presses and report which actions fired. Bindings with more
+    // modifiers are checked first so Ctrl+Shift+X doesn't also trigger a plain Ctrl+X binding.
+    pub fn triggered(&self, ctx: &egui::Context) -> Vec<Action> {
+        let mut actions = Action::ALL.to_vec();
+        actions.sort_by_key(|action| std::cmp::Reverse(self.get(*action).modifier_count()));
+
+        ctx.input_mut(|input| {
+            actions
+                .into_iter()
+                .filter(|action| input.consume_shortcut(&self.get(*action).shortcut()))
+                .collect()
+        })
+    }
+}
+
+// Read the next key press (with modifiers) for the rebinding UI; Escape cancels
+pub enum Capture {
+    Pending,
+    Cancelled,
+    Bound(KeyBinding),
+}
+
+pub fn capture_key(ctx: &egui::Context) -> Capture {
+    ctx.input(|input| {
+        for event in &input.events {
+            if let egui::Event::Key { key, pressed: true, modifiers, .. } = event {
+                if *key == egui::Key::Escape {
+                    return Capture::Cancelled;
+                }
+                return Capture::Bound(KeyBinding {
+                    key: *key,
+                    command: modifiers.command,
+                    shift: modifiers.shift,
+                    alt: modifiers.alt,
+                });
+            }
+        }
+        Capture::Pending
+    })
+}
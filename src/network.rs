@@ -0,0 +1,900 @@
+// This module contains the HTTP side of submitting card info
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::proxy_auth::{self, ProxyPassword};
+use crate::settings::Settings;
+use crate::{signing, unix_socket, websocket, CardInfo};
+
+const MAX_BODY_PREVIEW_CHARS: usize = 300; // Longest response body excerpt shown in the status line
+const MAX_BODY_PREVIEW_HEX_BYTES: usize = 32; // How many bytes of a binary body to show as hex
+
+pub const HTTP_METHODS: [&str; 3] = ["POST", "PUT", "PATCH"]; // Methods the card can be sent with
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const VERIFY_CERTIFICATES: bool = false; // WARNING: Only for testing with self-signed certs!
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500); // Doubled after every failed attempt
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Transport {
+    #[default]
+    Http,
+    WebSocket,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 2] = [Transport::Http, Transport::WebSocket];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transport::Http => "HTTP POST",
+            Transport::WebSocket => "WebSocket",
+        }
+    }
+}
+
+// Encoding of the request body for HTTP sends. WebSocket frames are always JSON text.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl BodyFormat {
+    pub const ALL: [BodyFormat; 3] = [BodyFormat::Json, BodyFormat::MessagePack, BodyFormat::Cbor];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "JSON",
+            BodyFormat::MessagePack => "MessagePack",
+            BodyFormat::Cbor => "CBOR",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::MessagePack => "application/msgpack",
+            BodyFormat::Cbor => "application/cbor",
+        }
+    }
+
+    // MessagePack is encoded as a map so field names survive, like JSON
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            BodyFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            BodyFormat::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            BodyFormat::Cbor => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("Couldn't encode card info as {}: {}", self.label(), e))
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            BodyFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            BodyFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            BodyFormat::Cbor => serde_cbor::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+
+    // Binary backends may answer in the format they were sent; anything else is tried as JSON
+    pub fn transaction_id(self, body: &[u8]) -> Option<String> {
+        self.decode::<serde_json::Value>(body)
+            .ok()
+            .and_then(|value| transaction_id_from(&value))
+            .or_else(|| extract_transaction_id(body))
+    }
+}
+
+// Network settings captured when a send starts, so background tasks don't touch the UI state
+#[derive(Clone, Debug)]
+pub struct SendConfig {
+    pub endpoint: String,
+    pub proxy: Option<String>, // As saved, without the password
+    pub proxy_password: Option<ProxyPassword>, // From the keyring
+    pub method: reqwest::Method,
+    pub body_format: BodyFormat,
+    pub transport: Transport,
+    pub websocket_url: String,
+    pub simulate_network_down: bool,
+    pub idempotency_key: Option<String>, // Sent as Idempotency-Key over HTTP so a resend isn't charged twice
+    pub timeouts: Timeouts,
+    pub retry: RetryPolicy,
+    pub max_response_body: usize, // Bytes; see ResponseBody
+    pub signing_key: Option<ed25519_dalek::SigningKey>, // HTTP bodies are signed when set
+    pub upload_progress: Option<Arc<UploadProgress>>, // Large HTTP bodies are streamed and counted when set
+}
+
+// Retries stop at whichever comes first: the retry count or the total time budget, which
+// covers the attempts themselves as well as the waits between them
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub budget: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            max_retries: settings.max_retries,
+            budget: Duration::from_secs(settings.retry_budget_seconds.into()),
+        }
+    }
+}
+
+// Kept apart so a server that never answers can be told from one that answers slowly
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub total: Duration,
+}
+
+impl Timeouts {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            connect: Duration::from_secs(settings.connect_timeout_seconds.into()),
+            total: Duration::from_secs(settings.request_timeout_seconds.into()),
+        }
+    }
+
+    // Name the timeout a failed request ran into, if it was one. reqwest flags connect
+    // timeouts as both a connect and a timeout error.
+    fn describe(&self, error: &reqwest::Error) -> Option<String> {
+        if !error.is_timeout() {
+            return None;
+        }
+        Some(if error.is_connect() {
+            format!("couldn't connect within {} s (connect timeout); the server may be unreachable", self.connect.as_secs())
+        } else {
+            format!("no response within {} s (request timeout); the server is slow to respond", self.total.as_secs())
+        })
+    }
+}
+
+impl SendConfig {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let proxy = settings.proxy_url.trim();
+        Self {
+            endpoint: settings.endpoint.trim().to_string(),
+            proxy: (!proxy.is_empty()).then(|| proxy.to_string()),
+            proxy_password: if proxy.is_empty() { None } else { load_proxy_password(proxy) },
+            method: parse_method(&settings.http_method).unwrap_or(reqwest::Method::POST),
+            body_format: settings.body_format,
+            transport: settings.transport,
+            websocket_url: settings.websocket_url.trim().to_string(),
+            simulate_network_down: settings.simulate_network_down,
+            idempotency_key: None,
+            timeouts: Timeouts::from_settings(settings),
+            retry: RetryPolicy::from_settings(settings),
+            max_response_body: settings.max_response_body_kib as usize * 1024,
+            signing_key: if settings.sign_requests { load_signing_key() } else { None },
+            upload_progress: None,
+        }
+    }
+
+    // The proxy URL for reqwest, with the password put back in
+    fn proxy_with_password(&self) -> Result<Option<Zeroizing<String>>, String> {
+        match (&self.proxy, &self.proxy_password) {
+            (Some(proxy), Some(password)) => proxy_auth::with_password(proxy, password).map(Some),
+            (proxy, _) => Ok(proxy.clone().map(Zeroizing::new)),
+        }
+    }
+
+    // Where a send will actually go, for status messages and history
+    pub fn destination(&self) -> &str {
+        match self.transport {
+            Transport::Http => &self.endpoint,
+            Transport::WebSocket => &self.websocket_url,
+        }
+    }
+}
+
+// What a successful submission returned that's worth showing
+pub struct SendSuccess {
+    pub transaction_id: Option<String>,
+    pub status: Option<u16>, // HTTP status, when the transport has one
+    pub connection: Option<ConnectionInfo>, // HTTP only
+    pub headers: ResponseHeaders,
+}
+
+// Response headers as received, in order, for the developer header panel. Empty when the
+// transport has none or no response arrived.
+pub type ResponseHeaders = Vec<(String, String)>;
+
+// Headers whose values are credentials or session state, hidden in the panel unless revealed
+const SENSITIVE_HEADERS: &[&str] = &["set-cookie", "cookie", "authorization", "proxy-authorization", "x-api-key"];
+
+pub fn is_sensitive_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SENSITIVE_HEADERS.contains(&name.as_str()) || name.contains("token") || name.contains("secret")
+}
+
+pub fn response_headers(headers: &reqwest::header::HeaderMap) -> ResponseHeaders {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect()
+}
+
+// What's known about the connection a successful request went over, for the lock tooltip.
+// reqwest doesn't report the negotiated TLS version, only the server's certificate.
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    pub https: bool,
+    pub http_version: String,
+    pub remote_addr: Option<std::net::SocketAddr>,
+    pub certificate_sha256: Option<String>, // Fingerprint of the server's leaf certificate
+    pub certificates_verified: bool,
+}
+
+impl ConnectionInfo {
+    fn from_response(res: &reqwest::Response) -> Self {
+        let certificate_sha256 = res
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .map(|der| {
+                let digest = ring::digest::digest(&ring::digest::SHA256, der);
+                digest.as_ref().iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(":")
+            });
+        Self {
+            https: res.url().scheme() == "https",
+            http_version: format!("{:?}", res.version()),
+            remote_addr: res.remote_addr(),
+            certificate_sha256,
+            certificates_verified: VERIFY_CERTIFICATES,
+        }
+    }
+
+    pub fn describe(info: Option<&ConnectionInfo>) -> String {
+        let Some(info) = info else {
+            return "Connection: unknown until the first successful request".to_string();
+        };
+        let mut lines = Vec::new();
+        if info.https {
+            lines.push("Encrypted (HTTPS): TLS 1.2 or 1.3, the HTTP client doesn't report which".to_string());
+        } else {
+            lines.push("Not encrypted: the endpoint uses plain HTTP".to_string());
+        }
+        lines.push(format!("Protocol: {}", info.http_version));
+        if let Some(addr) = info.remote_addr {
+            lines.push(format!("Server: {}", addr));
+        }
+        if let Some(fingerprint) = &info.certificate_sha256 {
+            lines.push(format!("Certificate SHA-256: {}", fingerprint));
+        }
+        lines.push(if info.certificates_verified {
+            "Certificate verification: on".to_string()
+        } else {
+            "Certificate verification: OFF (self-signed certificates are accepted)".to_string()
+        });
+        lines.join("\n")
+    }
+}
+
+// A failed submission: a one-line summary plus, when the server sent more than fits in it,
+// the whole rendered response body for a "show more" view
+#[derive(Debug)]
+pub struct SendError {
+    pub message: String,
+    pub details: Option<String>,
+    pub status: Option<u16>,
+    pub timed_out: bool, // Gave up waiting, as opposed to being refused or failing outright
+    pub unreachable: bool, // Couldn't connect, or the connection failed before an answer
+    pub headers: ResponseHeaders, // From the server's error response, if it sent one
+}
+
+impl SendError {
+    pub fn timeout(message: String) -> Self {
+        Self { message, details: None, status: None, timed_out: true, unreachable: false, headers: Vec::new() }
+    }
+
+    pub fn unreachable(message: String) -> Self {
+        Self { message, details: None, status: None, timed_out: false, unreachable: true, headers: Vec::new() }
+    }
+
+    // Nothing came back from the server. Anything else was an answer (a rejection, say) or a
+    // problem on this side, such as an invalid proxy or a missing URL.
+    pub fn is_transport_failure(&self) -> bool {
+        self.timed_out || self.unreachable
+    }
+
+    // Worth sending unchanged again: no answer, or the server failed rather than refused
+    pub fn is_retryable(&self) -> bool {
+        self.is_transport_failure() || self.status.is_some_and(|status| status >= 500)
+    }
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for SendError {
+    fn from(message: String) -> Self {
+        Self { message, details: None, status: None, timed_out: false, unreachable: false, headers: Vec::new() }
+    }
+}
+
+// Every request goes through here so sends, tests and diagnostics behave the same way.
+// Without an explicit proxy reqwest's usual system proxy detection applies, unless `direct` is set.
+fn build_client(proxy: Option<&str>, direct: bool, timeouts: Timeouts) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(timeouts.connect)
+        .timeout(timeouts.total)
+        .danger_accept_invalid_certs(!VERIFY_CERTIFICATES)
+        .tls_info(true);
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        builder = builder.proxy(proxy);
+    } else if direct {
+        builder = builder.no_proxy();
+    }
+    builder.build().map_err(|e| format!("Failed to build reqwest client: {}", e))
+}
+
+// Submit over whichever transport is configured, shared by the form, test card and batch mode.
+// Only failures that might go through unchanged (no connection, timeout, 5xx; see
+// SendError::is_retryable) are retried with backoff under config.retry. `on_status` receives
+// interim progress, including retries.
+pub async fn submit_card_info(
+    config: &SendConfig,
+    card_info: &CardInfo,
+    on_status: &(dyn Fn(String) + Send + Sync),
+) -> Result<SendSuccess, SendError> {
+    let RetryPolicy { max_retries, budget } = config.retry;
+    let started = Instant::now();
+    let mut attempt = 1;
+    loop {
+        let remaining = budget.saturating_sub(started.elapsed());
+        let error = match tokio::time::timeout(remaining, submit_once(config, card_info, on_status)).await {
+            Ok(Ok(success)) => return Ok(success),
+            Ok(Err(error)) => error,
+            Err(_) => {
+                tracing::warn!(attempt, "retry time budget used up mid-attempt");
+                return Err(SendError::timeout(format!(
+                    "Failed to send request: gave up during attempt {}: {} s retry time budget used up",
+                    attempt,
+                    budget.as_secs()
+                )))
+            }
+        };
+        if !error.is_retryable() || max_retries == 0 {
+            return Err(error);
+        }
+
+        let delay = RETRY_BASE_DELAY * 2u32.saturating_pow(attempt - 1);
+        let ended_by = if attempt > max_retries {
+            Some(format!("gave up after {} attempts: retry limit reached", attempt))
+        } else if started.elapsed() + delay >= budget {
+            Some(format!("gave up after {} attempts: {} s retry time budget used up", attempt, budget.as_secs()))
+        } else {
+            None
+        };
+        if let Some(ended_by) = ended_by {
+            tracing::warn!(attempt, status = ?error.status, timed_out = error.timed_out, "{}", ended_by);
+            return Err(SendError { message: format!("{} ({})", error.message, ended_by), ..error });
+        }
+
+        tracing::info!(attempt, status = ?error.status, timed_out = error.timed_out, delay_ms = delay.as_millis() as u64, "attempt failed, retrying");
+        on_status(format!("Attempt {} failed, retrying in {:.1} s…", attempt, delay.as_secs_f32()));
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+async fn submit_once(
+    config: &SendConfig,
+    card_info: &CardInfo,
+    on_status: &(dyn Fn(String) + Send + Sync),
+) -> Result<SendSuccess, SendError> {
+    if config.simulate_network_down {
+        return Err(SendError::unreachable("Failed to send request: simulated network failure (network down mode is on)".to_string()));
+    }
+    match config.transport {
+        Transport::Http if unix_socket::is_unix_endpoint(&config.endpoint) => unix_socket::post_card_info(config, card_info).await,
+        Transport::Http => post_card_info(config, card_info).await,
+        Transport::WebSocket => websocket::send_card_info(&config.websocket_url, card_info, on_status).await,
+    }
+}
+
+// The request a send would make, as text, with the CVV redacted. Built through the same client
+// as a real send so the headers match what goes on the wire.
+pub fn describe_raw_request(config: &SendConfig, card_info: &CardInfo) -> Result<String, String> {
+    let redacted = CardInfo { security_code: "•".repeat(card_info.security_code.chars().count()), ..card_info.clone() };
+    if config.transport == Transport::WebSocket {
+        let body = serde_json::to_string_pretty(&redacted).map_err(|e| e.to_string())?;
+        return Ok(format!("WebSocket {}\n\n{}", config.websocket_url, body));
+    }
+
+    // Headers, the signature included, are worked out over the real body; only the copy shown is redacted
+    let body = config.body_format.encode(card_info)?;
+    let shown = config.body_format.encode(&redacted)?;
+    // Socket requests are made by hand rather than through reqwest
+    if unix_socket::is_unix_endpoint(&config.endpoint) {
+        let mut text = unix_socket::describe_request(config, &body)?;
+        text.push_str(&describe_body(&shown, config.body_format));
+        return Ok(text);
+    }
+
+    let proxy = config.proxy_with_password()?;
+    let client = build_client(proxy.as_ref().map(|proxy| proxy.as_str()), false, config.timeouts)?;
+    let request = with_signature(with_idempotency_key(client.request(config.method.clone(), &config.endpoint), config), config, &body)
+        .header(reqwest::header::CONTENT_TYPE, config.body_format.content_type())
+        .build()
+        .map_err(|e| format!("Couldn't build the request: {}", e))?;
+
+    let mut text = format!("{} {} HTTP/1.1\n", request.method(), request.url());
+    for (name, value) in request.headers() {
+        text.push_str(&format!("{}: {}\n", name, value.to_str().unwrap_or("<binary>")));
+    }
+    text.push_str("(Host and Content-Length are added when the request is sent)\n\n");
+    text.push_str(&describe_body(&shown, config.body_format));
+    if let Some(proxy) = &config.proxy {
+        text.push_str(&format!("\n\nvia proxy {}", proxy));
+    }
+    Ok(text)
+}
+
+// JSON as text, anything else as a hex dump, 16 bytes per line
+fn describe_body(body: &[u8], format: BodyFormat) -> String {
+    if format == BodyFormat::Json {
+        return String::from_utf8_lossy(body).into_owned();
+    }
+    let mut text = format!("({} bytes of {})\n", body.len(), format.label());
+    for line in body.chunks(16) {
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+        text.push_str(&hex.join(" "));
+        text.push('\n');
+    }
+    text
+}
+
+async fn post_card_info(config: &SendConfig, card_info: &CardInfo) -> Result<SendSuccess, SendError> {
+    let proxy = config.proxy_with_password()?;
+    let client = build_client(proxy.as_ref().map(|proxy| proxy.as_str()), false, config.timeouts)?;
+    let body = config.body_format.encode(card_info)?;
+    let request = with_signature(with_idempotency_key(client.request(config.method.clone(), &config.endpoint), config), config, &body)
+        .header(reqwest::header::CONTENT_TYPE, config.body_format.content_type());
+    // Only bodies big enough to show progress for are streamed. reqwest can't replay a stream, so
+    // a streamed request that gets a 307 or 308 isn't followed; small ones go as plain bytes.
+    let res = match &config.upload_progress {
+        Some(progress) if body.len() as u64 >= UPLOAD_PROGRESS_MIN_BYTES => {
+            let length = body.len();
+            request
+                .header(reqwest::header::CONTENT_LENGTH, length)
+                .body(progress.counted_body(body))
+        }
+        _ => request.body(body),
+    }
+    .send()
+    .await;
+
+    match res {
+        Ok(res) => {
+            if res.status().is_success() {
+                let status = Some(res.status().as_u16());
+                let connection = Some(ConnectionInfo::from_response(&res));
+                let headers = response_headers(res.headers());
+                let body = read_body(res, config.max_response_body).await.unwrap_or_default();
+                Ok(SendSuccess { transaction_id: config.body_format.transaction_id(&body.bytes), status, connection, headers })
+            } else {
+                let status = res.status();
+                let headers = res.headers().clone();
+                // Read raw bytes so binary error pages don't lose the status context
+                let body = read_body(res, config.max_response_body).await;
+                Err(status_error(status, &headers, body.as_ref()))
+            }
+        },
+        Err(e) => match config.timeouts.describe(&e) {
+            Some(timeout) => Err(SendError::timeout(format!("Failed to send request: {}", timeout))),
+            None if e.is_connect() || e.is_request() => Err(SendError::unreachable(format!("Failed to send request: {}", e))),
+            // Builder errors such as a missing or malformed URL never reached the network
+            None => Err(format!("Failed to send request: {}", e).into()),
+        },
+    }
+}
+
+const UPLOAD_CHUNK_SIZE: usize = 16 * 1024;
+const UPLOAD_PROGRESS_MIN_BYTES: u64 = 64 * 1024; // Smaller bodies go out too fast to be worth showing
+
+// How much of the request body has been handed to the connection, for the sending overlay.
+// Shared with the send task, which counts each chunk as it's taken.
+#[derive(Debug, Default)]
+pub struct UploadProgress {
+    sent: AtomicU64,
+    total: AtomicU64, // 0 while the size isn't known
+}
+
+impl UploadProgress {
+    // Stream the body in chunks, counting each one as the connection takes it. Each attempt
+    // starts the count again.
+    fn counted_body(self: &Arc<Self>, body: Vec<u8>) -> reqwest::Body {
+        use futures_util::StreamExt;
+
+        self.sent.store(0, Ordering::Relaxed);
+        self.total.store(body.len() as u64, Ordering::Relaxed);
+        let progress = self.clone();
+        let chunks: Vec<bytes::Bytes> = body.chunks(UPLOAD_CHUNK_SIZE).map(bytes::Bytes::copy_from_slice).collect();
+        reqwest::Body::wrap_stream(futures_util::stream::iter(chunks).map(move |chunk| {
+            progress.sent.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            Ok::<_, std::io::Error>(chunk)
+        }))
+    }
+
+    // Bytes counted so far and the total, if known
+    pub fn counts(&self) -> (u64, Option<u64>) {
+        let total = self.total.load(Ordering::Relaxed);
+        (self.sent.load(Ordering::Relaxed), (total > 0).then_some(total))
+    }
+
+    // "Uploaded 40% (26 of 64 KiB)", or just the bytes when the size is unknown. None for small
+    // bodies and before anything has been counted.
+    pub fn label(&self) -> Option<String> {
+        let kib = |bytes: u64| bytes.div_ceil(1024);
+        let (sent, total) = match self.counts() {
+            (sent, None) => return (sent > 0).then(|| format!("Uploaded {} KiB", kib(sent))),
+            (_, Some(total)) if total < UPLOAD_PROGRESS_MIN_BYTES => return None,
+            (sent, Some(total)) => (sent, total),
+        };
+        Some(format!("Uploaded {}% ({} of {} KiB)", sent * 100 / total, kib(sent), kib(total)))
+    }
+}
+
+// A response body read as it streams in, up to a size limit. A misbehaving server can send an
+// endless error page, so whatever comes after the limit is left unread.
+#[derive(Default)]
+pub struct ResponseBody {
+    pub bytes: Vec<u8>,
+    pub truncated: bool,
+}
+
+impl ResponseBody {
+    // Returns false once the limit is reached and reading should stop
+    pub fn push(&mut self, chunk: &[u8], limit: usize) -> bool {
+        let room = limit.saturating_sub(self.bytes.len());
+        if chunk.len() > room {
+            self.bytes.extend_from_slice(&chunk[..room]);
+            self.truncated = true;
+            return false;
+        }
+        self.bytes.extend_from_slice(chunk);
+        true
+    }
+
+    // The limit can cut a multi-byte character in half; a text body cut that way is still text,
+    // so the incomplete tail is dropped rather than the whole body shown as hex
+    pub fn text_prefix(&self) -> &[u8] {
+        match std::str::from_utf8(&self.bytes) {
+            Err(e) if self.truncated && e.error_len().is_none() => &self.bytes[..e.valid_up_to()],
+            _ => &self.bytes,
+        }
+    }
+}
+
+async fn read_body(mut res: reqwest::Response, limit: usize) -> Option<ResponseBody> {
+    let mut body = ResponseBody::default();
+    while let Some(chunk) = res.chunk().await.ok()? {
+        if !body.push(&chunk, limit) {
+            break;
+        }
+    }
+    Some(body)
+}
+
+// Whether a redirect says where to go. reqwest follows the ones that do, so a 3xx with no
+// readable Location (or one that isn't a URL) is what reaches status_error.
+fn has_usable_location(headers: &reqwest::header::HeaderMap) -> bool {
+    let base = reqwest::Url::parse("http://localhost/").expect("valid base URL");
+    headers
+        .get(reqwest::header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .is_some_and(|location| !location.is_empty() && base.join(location).is_ok())
+}
+
+// The error for a non-2xx response, whichever transport it came over
+pub fn status_error(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: Option<&ResponseBody>) -> SendError {
+    if status.is_redirection() && status != reqwest::StatusCode::NOT_MODIFIED && !has_usable_location(headers) {
+        return SendError {
+            message: format!("Failed to send card info: Server returned a redirect without a location (Status {})", status),
+            details: None,
+            status: Some(status.as_u16()),
+            timed_out: false,
+            unreachable: false,
+            headers: response_headers(headers),
+        };
+    }
+    let content_type = headers.get(reqwest::header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let body = match body {
+        Some(body) if body.truncated => format!(
+            "{} (body truncated after {} bytes)",
+            render_response_body(body.text_prefix(), content_type),
+            body.bytes.len()
+        ),
+        Some(body) => render_response_body(&body.bytes, content_type),
+        None => "No response body".to_string(),
+    };
+    let (preview, truncated) = body_preview(&body);
+    SendError {
+        message: format!("Failed to send card info: Status {} - {}", status, preview),
+        details: truncated.then_some(body),
+        status: Some(status.as_u16()),
+        timed_out: false,
+        unreachable: false,
+        headers: response_headers(headers),
+    }
+}
+
+// Without a keyring the proxy is used with whatever credentials its URL has
+fn load_proxy_password(proxy: &str) -> Option<ProxyPassword> {
+    proxy_auth::load(proxy).unwrap_or_else(|e| {
+        tracing::warn!("using the proxy without a stored password: {}", e);
+        None
+    })
+}
+
+// With signing on but no key generated yet (or no keyring), requests simply go out unsigned
+fn load_signing_key() -> Option<ed25519_dalek::SigningKey> {
+    signing::load().unwrap_or_else(|e| {
+        tracing::warn!("sending unsigned: {}", e);
+        None
+    })
+}
+
+fn with_signature(mut request: reqwest::RequestBuilder, config: &SendConfig, body: &[u8]) -> reqwest::RequestBuilder {
+    if let Some(key) = &config.signing_key {
+        for (name, value) in signing::headers(key, body) {
+            request = request.header(name, value);
+        }
+    }
+    request
+}
+
+fn with_idempotency_key(request: reqwest::RequestBuilder, config: &SendConfig) -> reqwest::RequestBuilder {
+    match &config.idempotency_key {
+        Some(key) => request.header(IDEMPOTENCY_KEY_HEADER, key),
+        None => request,
+    }
+}
+
+// 128 random bits as hex, generated once per card and reused for every resend of it
+pub fn new_idempotency_key() -> String {
+    let mut bytes = [0u8; 16];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        tracing::warn!("failed to generate an idempotency key; falling back to the clock");
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        bytes = nanos.to_le_bytes();
+    }
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Only the methods in HTTP_METHODS are accepted, case-insensitively
+pub fn parse_method(method: &str) -> Option<reqwest::Method> {
+    let method = method.trim().to_ascii_uppercase();
+    HTTP_METHODS
+        .contains(&method.as_str())
+        .then(|| reqwest::Method::from_bytes(method.as_bytes()).ok())
+        .flatten()
+}
+
+// Scheme, host and port of an endpoint, for showing it outside the app. Credentials, path and
+// query are dropped since any of them might carry a secret.
+pub fn endpoint_origin(endpoint: &str) -> String {
+    if crate::unix_socket::is_unix_endpoint(endpoint) {
+        return "unix socket".to_string();
+    }
+    match reqwest::Url::parse(endpoint.trim()) {
+        Ok(url) => match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}://{}:{}", url.scheme(), host, port),
+            (Some(host), None) => format!("{}://{}", url.scheme(), host),
+            (None, _) => format!("{}:", url.scheme()),
+        },
+        Err(_) if endpoint.trim().is_empty() => "not set".to_string(),
+        Err(_) => "not a valid URL".to_string(),
+    }
+}
+
+// The endpoint with any user name and password removed, for keeping on disk. None for
+// something that isn't a URL at all.
+pub fn without_credentials(endpoint: &str) -> Option<String> {
+    let endpoint = endpoint.trim();
+    if crate::unix_socket::is_unix_endpoint(endpoint) {
+        return Some(endpoint.to_string());
+    }
+    let mut url = reqwest::Url::parse(endpoint).ok()?;
+    if !url.username().is_empty() || url.password().is_some() {
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        return Some(url.to_string());
+    }
+    Some(endpoint.to_string()) // Untouched, so a trailing slash or not stays as typed
+}
+
+// Time a bare request to the endpoint; any HTTP response counts as reachable
+async fn timed_probe(config: &SendConfig, proxy: Option<&str>) -> Result<(Duration, reqwest::StatusCode), String> {
+    let client = build_client(proxy, true, config.timeouts)?;
+    let started = Instant::now();
+    let res = client.head(&config.endpoint).send().await.map_err(|e| match config.timeouts.describe(&e) {
+        Some(timeout) => timeout,
+        None => error_chain(&e),
+    })?;
+    Ok((started.elapsed(), res.status()))
+}
+
+// The connection test on its own, through the configured proxy if there is one
+pub async fn probe_endpoint(config: &SendConfig) -> Result<(Duration, reqwest::StatusCode), String> {
+    let proxy = config.proxy_with_password()?;
+    timed_probe(config, proxy.as_ref().map(|proxy| proxy.as_str())).await
+}
+
+// Check the configured proxy can reach the endpoint and how much latency it adds
+pub async fn test_proxy(config: &SendConfig) -> Result<String, String> {
+    let Some(proxy) = config.proxy_with_password()? else {
+        return Err("No proxy configured".to_string());
+    };
+
+    let (proxied, status) = match timed_probe(config, Some(&proxy)).await {
+        Ok(result) => result,
+        Err(e) if is_proxy_auth_error(&e) => return Err(format!("Proxy authentication failed: {}", e)),
+        Err(e) => return Err(format!("Proxy unreachable: {}", e)),
+    };
+    if status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        return Err("Proxy authentication failed (407 Proxy Authentication Required)".to_string());
+    }
+
+    let proxied_ms = proxied.as_millis() as i64;
+    Ok(match timed_probe(config, None).await {
+        Ok((direct, _)) => {
+            let direct_ms = direct.as_millis() as i64;
+            format!(
+                "Proxy OK: {} ms via proxy vs {} ms direct ({:+} ms)",
+                proxied_ms,
+                direct_ms,
+                proxied_ms - direct_ms
+            )
+        }
+        Err(e) => format!("Proxy OK: {} ms via proxy (direct connection failed: {})", proxied_ms, e),
+    })
+}
+
+fn is_proxy_auth_error(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("407") || message.contains("proxy auth")
+}
+
+// reqwest errors hide the useful part (e.g. a tunnel failure) in their source chain
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+// Pull a transaction id out of a JSON success body, if the server sent one
+pub fn extract_transaction_id(body: &[u8]) -> Option<String> {
+    transaction_id_from(&serde_json::from_slice(body).ok()?)
+}
+
+fn transaction_id_from(json: &serde_json::Value) -> Option<String> {
+    ["transaction_id", "transactionId", "id"].iter().find_map(|key| match json.get(*key)? {
+        serde_json::Value::String(id) if !id.is_empty() => Some(id.clone()),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    })
+}
+
+// Turn a response body into readable text: JSON is pretty-printed, HTML reduced to its text,
+// and non-UTF-8 bodies summarised as hex
+fn render_response_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    if bytes.is_empty() {
+        return "No response body".to_string();
+    }
+    let content_type = content_type.unwrap_or("").to_ascii_lowercase();
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            if content_type.contains("json") {
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
+                    return serde_json::to_string_pretty(&json).unwrap_or_else(|_| text.to_string());
+                }
+            }
+            if content_type.contains("html") {
+                let extracted = html_to_text(text);
+                if !extracted.is_empty() {
+                    return extracted;
+                }
+            }
+            text.trim().to_string()
+        }
+        Err(_) => {
+            let hex: Vec<String> = bytes
+                .iter()
+                .take(MAX_BODY_PREVIEW_HEX_BYTES)
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let ellipsis = if bytes.len() > MAX_BODY_PREVIEW_HEX_BYTES { " …" } else { "" };
+            format!(
+                "<binary body, {} bytes, content type: {}> {}{}",
+                bytes.len(),
+                if content_type.is_empty() { "unknown" } else { &content_type },
+                hex.join(" "),
+                ellipsis
+            )
+        }
+    }
+}
+
+// Single-line excerpt for the status line, and whether it left anything out
+fn body_preview(body: &str) -> (String, bool) {
+    let single_line = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let shortened = single_line.chars().count() > MAX_BODY_PREVIEW_CHARS;
+    let preview = if shortened {
+        format!("{}…", single_line.chars().take(MAX_BODY_PREVIEW_CHARS).collect::<String>())
+    } else {
+        single_line
+    };
+    (preview, shortened || body.contains('\n'))
+}
+
+// Rough text extraction for HTML error pages: drops tags, scripts and styles, turns block
+// elements into line breaks and decodes the common entities
+fn html_to_text(html: &str) -> String {
+    const BLOCK_TAGS: &[&str] = &["p", "br", "div", "li", "tr", "h1", "h2", "h3", "h4", "h5", "h6", "title", "pre"];
+
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if (name == "script" || name == "style") && !tag.starts_with('/') {
+            // Skip straight past the closing tag
+            let closing = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&closing) {
+                Some(index) => &rest[index..],
+                None => "",
+            };
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,16 @@
+// This module contains the desktop notification used by "minimize on send" to report how a
+// background submission went. Only the status line is shown, which never has more than the
+// last four digits.
+
+const APP_NAME: &str = "creditcard";
+
+// Blocks while the notification service answers, so call it off the async workers
+pub fn show(succeeded: bool, text: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname(APP_NAME)
+        .summary(if succeeded { "Card sent" } else { "Card not sent" })
+        .body(text)
+        .show()
+        .map(|_| ())
+        .map_err(|e| format!("Desktop notifications are unavailable: {}", e))
+}
@@ -0,0 +1,88 @@
+// This module contains the optional per-field regular expressions, checked on top of the
+// built-in validation for backends with their own number or reference formats
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::validation::{self, Field, FieldIssue, Problem, ValidationResult};
+use crate::CardInfo;
+
+// As typed in the settings; an empty pattern means no extra check for that field
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(default)]
+pub struct FieldPatterns {
+    pub card_number: String,
+    pub cardholder_name: String,
+    pub expiry_date: String,
+    pub security_code: String,
+}
+
+impl FieldPatterns {
+    pub fn get(&self, field: Field) -> &str {
+        field.pick([&self.card_number, &self.cardholder_name, &self.expiry_date, &self.security_code])
+    }
+
+    pub fn get_mut(&mut self, field: Field) -> &mut String {
+        field.pick([&mut self.card_number, &mut self.cardholder_name, &mut self.expiry_date, &mut self.security_code])
+    }
+}
+
+// Compiled once per settings change rather than every frame. Patterns that don't compile are
+// kept aside with their error and otherwise ignored.
+#[derive(Clone, Debug, Default)]
+pub struct CompiledPatterns {
+    patterns: Vec<(Field, String, Regex)>,
+    errors: Vec<(Field, String)>,
+}
+
+impl CompiledPatterns {
+    pub fn compile(patterns: &FieldPatterns) -> Self {
+        let mut compiled = CompiledPatterns::default();
+        for field in Field::ALL {
+            let pattern = patterns.get(field).trim();
+            if pattern.is_empty() {
+                continue;
+            }
+            // The whole value has to match, not just some part of it. Compiled bare first so the
+            // error points into what the user wrote.
+            match Regex::new(pattern).and_then(|_| Regex::new(&format!("^(?:{})$", pattern))) {
+                Ok(regex) => compiled.patterns.push((field, pattern.to_string(), regex)),
+                Err(e) => {
+                    tracing::warn!("ignoring the {} pattern: {}", field.short_name(), e);
+                    compiled.errors.push((field, e.to_string()));
+                }
+            }
+        }
+        compiled
+    }
+
+    pub fn error(&self, field: Field) -> Option<&str> {
+        self.errors.iter().find(|(other, _)| *other == field).map(|(_, e)| e.as_str())
+    }
+
+    // The pattern a field failed, for showing next to the problem
+    pub fn pattern(&self, field: Field) -> Option<&str> {
+        self.patterns.iter().find(|(other, _, _)| *other == field).map(|(_, pattern, _)| pattern.as_str())
+    }
+
+    // Add a mismatch for every filled-in field that doesn't match its pattern. Empty fields are
+    // left to the built-in Missing check. Card numbers are matched without their separators.
+    pub fn check(&self, card_info: &CardInfo, result: &mut ValidationResult) {
+        for (field, _, regex) in &self.patterns {
+            let value = match field {
+                Field::CardNumber => validation::card_digits(&card_info.card_number),
+                field => card_info.field(*field).trim().to_string(),
+            };
+            if !value.is_empty() && !regex.is_match(&value) {
+                result.issues.push(FieldIssue { field: *field, problem: Problem::PatternMismatch });
+            }
+        }
+    }
+}
+
+// Built-in checks plus the custom patterns
+pub fn validate(card_info: &CardInfo, patterns: &CompiledPatterns) -> ValidationResult {
+    let mut result = validation::validate(card_info);
+    patterns.check(card_info, &mut result);
+    result
+}
@@ -0,0 +1,133 @@
+// This module contains the submission pipeline: validation, the go-ahead for warnings and the
+// confirmation step, the send itself (retries happen inside network::submit_card_info) and the
+// offline queue, composed into one call. The form, batch mode, headless mode and the self-test
+// all get the same SubmitOutcome back and react to it.
+
+use std::sync::Mutex;
+
+use tracing::Instrument;
+
+use crate::audit::AuditLog;
+use crate::network::{self, SendConfig, SendError, SendSuccess};
+use crate::patterns::{self, CompiledPatterns};
+use crate::queue::OfflineQueue;
+use crate::validation::{self, ValidationResult};
+use crate::CardInfo;
+
+pub struct Submission<'a> {
+    pub card_info: &'a CardInfo,
+    pub config: &'a SendConfig,
+    pub patterns: &'a CompiledPatterns,
+    pub allow_warnings: bool, // "Submit anyway" was chosen, or there's nobody to ask
+    pub confirmed: bool, // The review/confirmation step has been passed, or isn't wanted
+    pub queue: Option<&'a Mutex<OfflineQueue>>, // Where unanswered sends go; None when queueing is off
+    pub audit: Option<&'a AuditLog>, // Gets a line for every send that went out, when it's turned on
+}
+
+// What the user is being asked for before anything is sent
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Awaiting {
+    Warnings, // Soft warnings to accept with "Submit anyway"
+    Confirmation, // The confirm or raw-request review window
+}
+
+pub enum SubmitOutcome {
+    Rejected(ValidationResult), // Hard validation errors; nothing was sent
+    Validated(ValidationResult, Awaiting), // Valid, but not sent until the user goes ahead
+    Sent(SendSuccess),
+    Queued(SendError), // No answer; held in the offline queue to be retried
+    Failed(SendError),
+}
+
+impl SubmitOutcome {
+    // Short name for logs and the self-test
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SubmitOutcome::Rejected(_) => "rejected",
+            SubmitOutcome::Validated(..) => "validated",
+            SubmitOutcome::Sent(_) => "sent",
+            SubmitOutcome::Queued(_) => "queued",
+            SubmitOutcome::Failed(_) => "failed",
+        }
+    }
+
+    // The network result, for sends that actually went out (queued ones included)
+    pub fn send_result(&self) -> Option<Result<&SendSuccess, &SendError>> {
+        match self {
+            SubmitOutcome::Sent(success) => Some(Ok(success)),
+            SubmitOutcome::Queued(e) | SubmitOutcome::Failed(e) => Some(Err(e)),
+            SubmitOutcome::Rejected(_) | SubmitOutcome::Validated(..) => None,
+        }
+    }
+}
+
+// Everything short of the network. None means the card may be sent now.
+pub fn check(submission: &Submission) -> Option<SubmitOutcome> {
+    let validation = patterns::validate(submission.card_info, submission.patterns);
+    if validation.is_blocked() {
+        return Some(SubmitOutcome::Rejected(validation));
+    }
+    if validation.has_warnings() && !submission.allow_warnings {
+        return Some(SubmitOutcome::Validated(validation, Awaiting::Warnings));
+    }
+    if !submission.confirmed {
+        return Some(SubmitOutcome::Validated(validation, Awaiting::Confirmation));
+    }
+    None
+}
+
+// Every log line about one submission (its attempts, retries and result) is in this span. The
+// idempotency key is shared by everything that resends the same card, and the transaction id is
+// filled in once the server has given one. Only the masked number is recorded, never the PAN or
+// CVV; error messages can echo the request, so log lines carry statuses instead.
+pub fn span(card_info: &CardInfo, config: &SendConfig) -> tracing::Span {
+    tracing::info_span!(
+        "submission",
+        key = config.idempotency_key.as_deref().unwrap_or("-"),
+        card = %validation::masked_card_number(&card_info.card_number),
+        endpoint = %network::endpoint_origin(config.destination()),
+        transaction_id = tracing::field::Empty,
+    )
+}
+
+pub async fn submit(submission: &Submission<'_>, on_status: &(dyn Fn(String) + Send + Sync)) -> SubmitOutcome {
+    // Join the caller's span if it already opened one for this submission
+    let current = tracing::Span::current();
+    let span = match current.metadata() {
+        Some(metadata) if metadata.name() == "submission" => current,
+        _ => span(submission.card_info, submission.config),
+    };
+    let outcome = run(submission, on_status, &span).instrument(span.clone()).await;
+    if let (Some(audit), Some(result)) = (submission.audit, outcome.send_result()) {
+        audit.record(submission.card_info, submission.config, outcome.kind(), result);
+    }
+    outcome
+}
+
+async fn run(submission: &Submission<'_>, on_status: &(dyn Fn(String) + Send + Sync), span: &tracing::Span) -> SubmitOutcome {
+    if let Some(outcome) = check(submission) {
+        tracing::info!(outcome = outcome.kind(), "not sent");
+        return outcome;
+    }
+    tracing::info!("sending");
+    match network::submit_card_info(submission.config, submission.card_info, on_status).await {
+        Ok(success) => {
+            if let Some(transaction_id) = &success.transaction_id {
+                span.record("transaction_id", transaction_id.as_str());
+            }
+            tracing::info!(status = ?success.status, "sent");
+            SubmitOutcome::Sent(success)
+        }
+        Err(e) => match (submission.queue, &submission.config.idempotency_key) {
+            (Some(queue), Some(key)) if OfflineQueue::should_queue(&e) => {
+                tracing::warn!(timed_out = e.timed_out, "no answer, queued for a retry");
+                queue.lock().unwrap().push(submission.card_info.clone(), key.clone(), e.message.clone());
+                SubmitOutcome::Queued(e)
+            }
+            _ => {
+                tracing::warn!(status = ?e.status, timed_out = e.timed_out, "failed");
+                SubmitOutcome::Failed(e)
+            }
+        },
+    }
+}
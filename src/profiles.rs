@@ -0,0 +1,82 @@
+// This module contains named settings profiles, e.g. one per test environment.
+// Profiles hold settings and an optional cardholder name only, never card numbers or CVVs.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::settings::{self, Settings};
+
+const PROFILES_FILE_NAME: &str = "profiles.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Settings,
+    #[serde(default)]
+    pub default_cardholder_name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ProfileStore {
+    pub profiles: Vec<Profile>,
+    pub active: Option<String>,
+}
+
+impl ProfileStore {
+    pub fn load() -> Self {
+        settings::load_or_default(profiles_path())
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if settings::memory_only() {
+            return Ok(());
+        }
+        let path = profiles_path()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No config directory available"))?;
+        let contents = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        settings::save_atomic(&path, contents.as_bytes())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    // Add a profile, replacing any existing one with the same name
+    pub fn upsert(&mut self, profile: Profile) {
+        match self.profiles.iter_mut().find(|existing| existing.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if to.is_empty() {
+            return Err("Profile name can't be empty".to_string());
+        }
+        if from != to && self.get(to).is_some() {
+            return Err(format!("A profile named \"{}\" already exists", to));
+        }
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == from)
+            .ok_or_else(|| format!("No profile named \"{}\"", from))?;
+        profile.name = to.to_string();
+        if self.active.as_deref() == Some(from) {
+            self.active = Some(to.to_string());
+        }
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) {
+        self.profiles.retain(|profile| profile.name != name);
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+    }
+}
+
+fn profiles_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(PROFILES_FILE_NAME))
+}
@@ -0,0 +1,186 @@
+// This module contains the offline queue: form sends that failed without a server answer
+// (no connection, timeout) are held here and retried until they go through, are removed, or run
+// out of attempts.
+// The queue lives in memory only, so card details, the CVV included, never touch the disk.
+
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use zeroize::Zeroizing;
+
+use crate::audit::AuditLog;
+use crate::history::HistoryEntry;
+use crate::network::{SendConfig, SendError};
+use crate::patterns::CompiledPatterns;
+use crate::pipeline::{self, Submission, SubmitOutcome};
+use crate::{validation, CardInfo};
+
+pub const RETRY_INTERVAL_SECONDS: f64 = 30.0; // Pause between automatic retries of the whole queue
+pub const MAX_ATTEMPTS: u32 = 10; // Sends of one card, the original included, before it's given up on
+
+pub struct QueuedCard {
+    pub id: u64,
+    pub card_info: Zeroizing<CardInfo>, // Wiped when it leaves the queue
+    pub idempotency_key: String, // Kept from the original send so the server can spot duplicates
+    pub queued_at: chrono::DateTime<chrono::Local>,
+    pub attempts: u32,
+    pub last_error: String,
+}
+
+// A card that has left the queue on its own, sent or given up on, for the UI to report
+pub struct Settled {
+    pub entry: HistoryEntry,
+    pub attempts: u32,
+}
+
+#[derive(Default)]
+pub struct OfflineQueue {
+    entries: Vec<QueuedCard>,
+    next_id: u64,
+    last_error: Option<String>,
+    draining: bool,
+    settled: Vec<Settled>, // Collected by the UI each frame
+}
+
+impl OfflineQueue {
+    // Only failures where the server never answered are worth retrying unchanged; a rejection or
+    // a configuration problem would fail the same way every time
+    pub fn should_queue(error: &SendError) -> bool {
+        error.is_transport_failure()
+    }
+
+    pub fn push(&mut self, card_info: CardInfo, idempotency_key: String, error: String) {
+        self.next_id += 1;
+        self.last_error = Some(error.clone());
+        self.entries.push(QueuedCard {
+            id: self.next_id,
+            card_info: Zeroizing::new(card_info),
+            idempotency_key,
+            queued_at: chrono::Local::now(),
+            attempts: 1,
+            last_error: error,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &QueuedCard> {
+        self.entries.iter()
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.is_empty() {
+            self.last_error = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.last_error = None;
+    }
+
+    pub fn take_settled(&mut self) -> Vec<Settled> {
+        std::mem::take(&mut self.settled)
+    }
+
+    fn settle(&mut self, id: u64, endpoint: &str, outcome: Result<Option<String>, String>) {
+        let Some(index) = self.entries.iter().position(|entry| entry.id == id) else {
+            return;
+        };
+        let entry = self.entries.remove(index);
+        if self.entries.is_empty() {
+            self.last_error = None;
+        }
+        self.settled.push(Settled {
+            entry: HistoryEntry {
+                submitted_at: chrono::Local::now(),
+                masked_number: validation::masked_card_number(&entry.card_info.card_number),
+                endpoint: endpoint.to_string(),
+                outcome,
+            },
+            attempts: entry.attempts,
+        });
+    }
+}
+
+// Retry every queued card once, in order, in the background, through the same pipeline as a
+// fresh send. Cards that go through leave the queue, and so do ones that get a real answer or
+// reach MAX_ATTEMPTS; the rest keep their place with the new error. Does nothing if a retry is
+// running.
+pub fn drain(
+    queue: Arc<Mutex<OfflineQueue>>,
+    config: SendConfig,
+    patterns: CompiledPatterns,
+    audit: Option<AuditLog>,
+    ctx: egui::Context,
+) {
+    let pending: Vec<(u64, Zeroizing<CardInfo>, String)> = {
+        let mut queue = queue.lock().unwrap();
+        if queue.draining || queue.entries.is_empty() {
+            return;
+        }
+        queue.draining = true;
+        queue
+            .entries
+            .iter()
+            .map(|entry| (entry.id, entry.card_info.clone(), entry.idempotency_key.clone()))
+            .collect()
+    };
+
+    tokio::spawn(async move {
+        for (id, card_info, idempotency_key) in pending {
+            // Same key as when it was queued, so the retry lands in the same log trail. It was
+            // confirmed when first sent; a failure here updates its entry instead of queueing again.
+            let config = SendConfig { idempotency_key: Some(idempotency_key), ..config.clone() };
+            let submission = Submission {
+                card_info: &card_info,
+                config: &config,
+                patterns: &patterns,
+                allow_warnings: true,
+                confirmed: true,
+                queue: None,
+                audit: audit.as_ref(),
+            };
+            let outcome = pipeline::submit(&submission, &|_| {}).await;
+            let mut queue = queue.lock().unwrap();
+            let endpoint = config.destination();
+            match outcome {
+                SubmitOutcome::Sent(success) => queue.settle(id, endpoint, Ok(success.transaction_id)),
+                SubmitOutcome::Queued(e) | SubmitOutcome::Failed(e) => {
+                    let Some(entry) = queue.entries.iter_mut().find(|entry| entry.id == id) else {
+                        continue; // Removed by hand while it was being sent
+                    };
+                    entry.attempts += 1;
+                    entry.last_error = e.message.clone();
+                    if !OfflineQueue::should_queue(&e) || entry.attempts >= MAX_ATTEMPTS {
+                        queue.settle(id, endpoint, Err(e.message));
+                    } else {
+                        queue.last_error = Some(e.message);
+                    }
+                }
+                // The field patterns changed since it was queued and it no longer passes
+                SubmitOutcome::Rejected(validation) | SubmitOutcome::Validated(validation, _) => {
+                    queue.settle(id, endpoint, Err(validation.error_summary()))
+                }
+            }
+            ctx.request_repaint();
+        }
+        queue.lock().unwrap().draining = false;
+        ctx.request_repaint();
+    });
+}
@@ -0,0 +1,57 @@
+// This module contains the card reader extension point: anything that can produce a CardInfo
+// without the user typing it in. Hardware readers plug in by implementing CardReader and adding
+// a ReaderKind variant.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{import, CardInfo};
+
+pub trait CardReader {
+    fn read(&self) -> Result<CardInfo, String>;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ReaderKind {
+    #[default]
+    Manual,
+    File, // Mock device that "reads" a card from a JSON file
+}
+
+impl ReaderKind {
+    pub const ALL: [ReaderKind; 2] = [ReaderKind::Manual, ReaderKind::File];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReaderKind::Manual => "Manual entry",
+            ReaderKind::File => "File (mock reader)",
+        }
+    }
+}
+
+// Default reader: there's no device, the user types the card in
+pub struct ManualEntry;
+
+impl CardReader for ManualEntry {
+    fn read(&self) -> Result<CardInfo, String> {
+        Err("No card reader configured; enter the card details manually".to_string())
+    }
+}
+
+// Reads a single CardInfo JSON object from disk, standing in for a hardware reader in tests
+pub struct FileReader {
+    pub path: PathBuf,
+}
+
+impl CardReader for FileReader {
+    fn read(&self) -> Result<CardInfo, String> {
+        import::load_card_file(&self.path)
+    }
+}
+
+pub fn create(kind: ReaderKind, file_path: &str) -> Box<dyn CardReader> {
+    match kind {
+        ReaderKind::Manual => Box::new(ManualEntry),
+        ReaderKind::File => Box::new(FileReader { path: PathBuf::from(file_path.trim()) }),
+    }
+}
@@ -2,3 +2,18 @@
 
 // Embed the image directly in the binary
 pub const EMBEDDED_IMAGE: &[u8] = include_bytes!("leftimage.jpg");
+
+// JSON Schema that imported card files must satisfy before they're applied to the form
+pub const CARD_INFO_SCHEMA: &str = r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "title": "CardInfo",
+    "type": "object",
+    "properties": {
+        "card_number": { "type": "string" },
+        "cardholder_name": { "type": "string" },
+        "expiry_date": { "type": "string" },
+        "security_code": { "type": "string" }
+    },
+    "required": ["card_number", "expiry_date", "security_code"],
+    "additionalProperties": false
+}"#;
@@ -0,0 +1,141 @@
+// This module contains the segmented card number entry: four boxes that together hold the
+// number, like some payment forms. The boxes are only a view of the one card number string, so
+// edits, pastes and deletions flow across them the way they would in a single text box.
+
+use eframe::egui;
+
+use crate::validation;
+
+// Three groups of four, then the rest of a 19-digit number in the last box
+const SEGMENT_LENGTHS: [usize; 4] = [4, 4, 4, 7];
+const MAX_DIGITS: usize = 19;
+
+pub struct SegmentedResponse {
+    pub response: egui::Response, // The focused box (or the first), stretched over all four
+    pub cursor_at_end: bool, // For auto-advance: the cursor is after the last digit
+}
+
+// Where each box's digits start in the whole number
+fn segment_starts() -> [usize; 4] {
+    let mut starts = [0; 4];
+    for i in 1..4 {
+        starts[i] = starts[i - 1] + SEGMENT_LENGTHS[i - 1];
+    }
+    starts
+}
+
+// Box and offset for a position in the whole number. At a boundary, typing moves on to the
+// start of the next box while deleting stays at the end of the previous one.
+fn locate(position: usize, forward: bool) -> (usize, usize) {
+    let starts = segment_starts();
+    for i in (0..4).rev() {
+        let at_boundary = position == starts[i] && i > 0 && !forward;
+        if position >= starts[i] && !at_boundary {
+            return (i, position - starts[i]);
+        }
+    }
+    (0, position)
+}
+
+// The number after box `index` was edited to `text`, and where the cursor goes: box and offset.
+// The edited box can take any number of digits (a paste, say); they spill over into the boxes
+// after it. `cursor` is the char offset in `text`.
+pub fn edit(digits: &str, index: usize, text: &str, cursor: usize) -> (String, (usize, usize)) {
+    let digits: Vec<char> = digits.chars().collect();
+    let start = segment_starts()[index];
+    let before_len = digits.iter().skip(start).take(SEGMENT_LENGTHS[index]).count();
+    let typed_before_cursor = text.chars().take(cursor).filter(char::is_ascii_digit).count();
+    let mut new_digits: Vec<char> = digits.iter().take(start).copied().collect();
+    new_digits.extend(text.chars().filter(char::is_ascii_digit));
+    new_digits.extend(digits.iter().skip(start + before_len));
+    new_digits.truncate(MAX_DIGITS);
+    let position = (start + typed_before_cursor).min(new_digits.len());
+    let forward = new_digits.len() > digits.len();
+    (new_digits.into_iter().collect(), locate(position, forward))
+}
+
+// Backspace at the start of box `index` deletes the last digit before it
+pub fn backspace(digits: &str, index: usize) -> Option<(String, (usize, usize))> {
+    let mut digits: Vec<char> = digits.chars().collect();
+    let position = segment_starts()[index].min(digits.len());
+    if position == 0 {
+        return None;
+    }
+    digits.remove(position - 1);
+    Some((digits.into_iter().collect(), locate(position - 1, false)))
+}
+
+fn place_cursor(ctx: &egui::Context, id: egui::Id, offset: usize) {
+    let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    let cursor = egui::text::CCursor::new(offset);
+    state.cursor.set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+    state.store(ctx, id);
+    ctx.memory_mut(|memory| memory.request_focus(id));
+}
+
+fn cursor_offset(ctx: &egui::Context, id: egui::Id) -> Option<usize> {
+    egui::TextEdit::load_state(ctx, id)
+        .and_then(|state| state.cursor.char_range())
+        .filter(|range| range.primary == range.secondary)
+        .map(|range| range.primary.index)
+}
+
+// `card_number` is rewritten as plain digits once a box is edited; until then it keeps whatever
+// formatting it had. `mask` draws the boxes as password fields.
+pub fn show(ui: &mut egui::Ui, id_salt: &str, card_number: &mut String, mask: bool, text_color: egui::Color32) -> SegmentedResponse {
+    let ctx = ui.ctx().clone();
+    let base_id = egui::Id::new(id_salt);
+    let digits = validation::card_digits(card_number);
+    let starts = segment_starts();
+
+    let mut responses = Vec::with_capacity(4);
+    let mut edited = None; // The new number and where its cursor goes
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 4.0;
+        for (i, &start) in starts.iter().enumerate() {
+            let id = base_id.with(i);
+            let mut text: String = digits.chars().skip(start).take(SEGMENT_LENGTHS[i]).collect();
+            let width = if i == 3 { 58.0 } else { 36.0 };
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut text)
+                    .id(id)
+                    .desired_width(width)
+                    .text_color(text_color)
+                    .frame(true)
+                    .password(mask),
+            );
+            if response.changed() {
+                let cursor = cursor_offset(&ctx, id).unwrap_or(text.chars().count());
+                edited = Some(edit(&digits, i, &text, cursor));
+            } else if response.has_focus()
+                && cursor_offset(&ctx, id) == Some(0)
+                && ui.input(|input| input.key_pressed(egui::Key::Backspace))
+            {
+                edited = backspace(&digits, i);
+            }
+            responses.push(response);
+        }
+    });
+
+    let cursor_at_end = edited.as_ref().is_some_and(|(new_digits, (box_index, offset))| {
+        starts[*box_index] + offset == new_digits.chars().count()
+    });
+    if let Some((new_digits, (box_index, offset))) = &edited {
+        *card_number = new_digits.clone();
+        place_cursor(&ctx, base_id.with(*box_index), *offset);
+    }
+
+    // Moving between the boxes isn't leaving the field: only report lost focus once none of
+    // them has it
+    let index = responses
+        .iter()
+        .position(egui::Response::has_focus)
+        .or_else(|| responses.iter().position(egui::Response::lost_focus))
+        .unwrap_or(0);
+    let mut response = responses[index].clone();
+    response.rect = responses.iter().fold(response.rect, |rect, other| rect.union(other.rect));
+    if edited.is_some() {
+        response.mark_changed();
+    }
+    SegmentedResponse { response, cursor_at_end }
+}
@@ -0,0 +1,452 @@
+// This module contains the user settings and their persistence
+
+use eframe::egui;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::animation;
+use crate::keybindings::KeyBindings;
+use crate::network::{self, BodyFormat, Transport};
+use crate::patterns::FieldPatterns;
+use crate::proxy_auth;
+use crate::reader::ReaderKind;
+use crate::template;
+use crate::theme::Theme;
+use crate::validation::{ExpiryFormat, Field, ValidationTiming};
+
+const APP_DIR_NAME: &str = "creditcard";
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+pub const DEFAULT_ENDPOINT: &str = "https://slipstreamm.dev/api/card";
+pub const RECENT_ENDPOINTS_LIMIT: usize = 10;
+pub const IDLE_LOCK_SECONDS_RANGE: std::ops::RangeInclusive<u32> = 15..=3600;
+pub const MIN_WINDOW_OPACITY: f32 = 0.4; // Below this the form becomes hard to read
+pub const HISTORY_LIMIT_RANGE: std::ops::RangeInclusive<usize> = 0..=1000; // 0 disables history
+pub const HISTORY_PANEL_HEIGHT_RANGE: std::ops::RangeInclusive<f32> = 60.0..=400.0;
+pub const TIMEOUT_SECONDS_RANGE: std::ops::RangeInclusive<u32> = 1..=300;
+pub const MAX_RETRIES_RANGE: std::ops::RangeInclusive<u32> = 0..=10;
+pub const RETRY_BUDGET_SECONDS_RANGE: std::ops::RangeInclusive<u32> = 1..=600;
+pub const RESPONSE_BODY_KIB_RANGE: std::ops::RangeInclusive<u32> = 1..=65536;
+pub const AUDIT_LOG_MAX_KIB_RANGE: std::ops::RangeInclusive<u32> = 16..=1048576;
+pub const FALLBACK_IMAGE_SIZE_RANGE: std::ops::RangeInclusive<f32> = 50.0..=600.0;
+pub const ANIMATION_FPS_RANGE: std::ops::RangeInclusive<u32> = 10..=120;
+pub const DEFAULT_SUBMIT_LABEL: &str = "Th-thanks";
+pub const SUBMIT_TEXT_SIZE_RANGE: std::ops::RangeInclusive<f32> = 9.0..=24.0;
+const DEFAULT_SUBMIT_TEXT_SIZE: f32 = 13.0; // The button's 100x25 size is scaled from this
+
+// Title bar gradient presets as (name, top color, bottom color)
+pub const TITLE_BAR_PRESETS: [(&str, [u8; 3], [u8; 3]); 3] = [
+    ("Classic blue", [225, 234, 254], [196, 213, 242]),
+    ("Graphite", [222, 222, 226], [172, 174, 180]),
+    ("Teal", [212, 241, 238], [150, 202, 197]),
+];
+
+// When a validated card needs an explicit "Send" click. A threshold on the amount can join
+// these once the form has an amount field.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SendConfirmation {
+    #[default]
+    Always,
+    Never,
+}
+
+impl SendConfirmation {
+    pub const ALL: [SendConfirmation; 2] = [SendConfirmation::Always, SendConfirmation::Never];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SendConfirmation::Always => "Always",
+            SendConfirmation::Never => "Never",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)] // Missing keys fall back to defaults so older settings files keep loading
+pub struct Settings {
+    pub endpoint: String,
+    pub recent_endpoints: Vec<String>, // Most recent first, offered under the endpoint field; never with credentials
+    pub http_method: String, // One of network::HTTP_METHODS
+    pub body_format: BodyFormat,
+    #[serde(serialize_with = "serialize_proxy_url")]
+    pub proxy_url: String, // Empty means connect directly; a password typed into it is moved to the keyring
+    pub connect_timeout_seconds: u32, // How long to wait for the server to accept the connection
+    pub request_timeout_seconds: u32, // Whole request, connecting included
+    pub max_retries: u32, // Extra attempts after a send that got no usable answer; 0 disables retries
+    pub retry_budget_seconds: u32, // Total time for all attempts and the waits between them
+    pub max_response_body_kib: u32, // Anything a server sends past this is never read
+    pub sign_requests: bool, // Sign HTTP bodies with the Ed25519 key from the keyring, if one exists
+    pub transport: Transport,
+    pub websocket_url: String, // wss:// URL used when the transport is WebSocket
+    pub window_opacity: f32,
+    pub theme: Theme,
+    pub animate_theme_changes: bool,
+    #[serde(default = "animation::os_prefers_reduced_motion")]
+    pub reduce_motion: bool, // Skip the theme blend and hover easing; defaults to the OS preference
+    pub animation_fps: u32, // Frame rate of the theme blend and spinners; lower saves CPU
+    pub brand_accent: bool, // Tint the submit button with the detected card brand's color
+    pub submit_label: String, // Empty falls back to DEFAULT_SUBMIT_LABEL
+    pub submit_text_size: f32,
+    pub mask_when_unfocused: bool, // Hide the CVV while another app has focus
+    pub mask_card_number_when_unfocused: bool, // Also hide the card number in that case
+    pub keybindings: KeyBindings,
+    pub reader_kind: ReaderKind,
+    pub reader_file_path: String, // Used by the file-backed mock reader
+    pub custom_image_path: String, // Replaces the character image; empty uses the embedded one
+    pub fallback_image_path: String, // Shown if no character image decodes; empty goes straight to the placeholder
+    pub fallback_image_size: [f32; 2], // Display size of the fallback image or placeholder
+    pub fallback_image_text: bool, // Overlay the "failed to load" text on it
+    pub title_bar_top_color: [u8; 3],
+    pub title_bar_bottom_color: [u8; 3],
+    pub lock_window: bool, // Kiosk mode: the title bar doesn't move the window and there's no close or minimize
+    pub idle_lock_enabled: bool,
+    pub idle_lock_seconds: u32,
+    pub idle_lock_clears_all: bool, // Clear every field rather than just the CVV
+    pub validation_timing: ValidationTiming,
+    pub field_patterns: FieldPatterns, // Extra regexes a field has to match, on top of the built-in checks
+    pub auto_advance: bool, // Move to the next field once the card number or expiry is complete
+    pub segmented_card_number: bool, // Enter the card number in four boxes instead of one field
+    pub field_order: Vec<Field>, // Form rows top to bottom; every field exactly once
+    pub expiry_format: ExpiryFormat, // Year style sent to the server, whichever the user typed
+    pub send_formatted_card_number: bool, // Send the number with its spaces/dashes instead of digits only
+    pub send_confirmation: SendConfirmation,
+    pub success_message_template: String, // See template::PLACEHOLDERS
+    pub error_message_template: String,
+    pub simulate_network_down: bool, // Developer option: every send fails without touching the network
+    pub review_raw_request: bool, // Developer option: show the exact request and confirm before sending
+    pub inspect_response_headers: bool, // Developer option: offer the last response's headers
+    pub queue_failed_sends: bool, // Hold sends that got no answer in memory and retry them
+    pub minimize_on_send: bool, // Minimize on submit and report the result as a desktop notification
+    pub send_on_paste: bool, // Submit a complete, valid pasted card after a short countdown
+    pub save_drafts: bool, // Keep the unsent form (minus the CVV) encrypted on disk between runs
+    pub watermark_enabled: bool, // Diagonal "CONFIDENTIAL" + timestamp over the whole window
+    pub history_limit: usize,
+    pub persist_history: bool, // Keep the (masked) history between runs
+    pub telemetry_enabled: bool, // Record local-only usage stats
+    pub audit_log_enabled: bool, // Append a masked JSON line per send to the audit log
+    pub audit_log_path: String, // Empty means audit.jsonl in the config directory
+    pub audit_log_max_kib: u32, // Size at which the log is rotated to a ".1" backup
+    pub export_directory: String, // Empty means an "exports" folder in the config directory
+    pub history_panel_open: bool,
+    pub history_panel_height: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            recent_endpoints: Vec::new(),
+            http_method: "POST".to_string(),
+            body_format: BodyFormat::Json,
+            proxy_url: String::new(),
+            connect_timeout_seconds: 5,
+            request_timeout_seconds: 15,
+            max_retries: 2,
+            retry_budget_seconds: 30,
+            max_response_body_kib: 1024,
+            sign_requests: false,
+            transport: Transport::default(),
+            websocket_url: String::new(),
+            window_opacity: 1.0,
+            theme: Theme::default(),
+            animate_theme_changes: true,
+            reduce_motion: animation::os_prefers_reduced_motion(),
+            animation_fps: 60,
+            brand_accent: true,
+            submit_label: DEFAULT_SUBMIT_LABEL.to_string(),
+            submit_text_size: DEFAULT_SUBMIT_TEXT_SIZE,
+            mask_when_unfocused: true,
+            mask_card_number_when_unfocused: false,
+            keybindings: KeyBindings::default(),
+            reader_kind: ReaderKind::default(),
+            reader_file_path: String::new(),
+            custom_image_path: String::new(),
+            fallback_image_path: String::new(),
+            fallback_image_size: [150.0, 200.0],
+            fallback_image_text: true,
+            title_bar_top_color: TITLE_BAR_PRESETS[0].1,
+            title_bar_bottom_color: TITLE_BAR_PRESETS[0].2,
+            lock_window: false,
+            idle_lock_enabled: true,
+            idle_lock_seconds: 300,
+            idle_lock_clears_all: false,
+            validation_timing: ValidationTiming::default(),
+            field_patterns: FieldPatterns::default(),
+            segmented_card_number: false,
+            field_order: Field::ALL.to_vec(),
+            auto_advance: false,
+            expiry_format: ExpiryFormat::default(),
+            send_formatted_card_number: false,
+            send_confirmation: SendConfirmation::default(),
+            success_message_template: template::DEFAULT_SUCCESS_TEMPLATE.to_string(),
+            error_message_template: template::DEFAULT_ERROR_TEMPLATE.to_string(),
+            simulate_network_down: false,
+            review_raw_request: false,
+            inspect_response_headers: false,
+            queue_failed_sends: false,
+            minimize_on_send: false,
+            send_on_paste: false,
+            save_drafts: false,
+            watermark_enabled: false,
+            history_limit: 50,
+            persist_history: false,
+            telemetry_enabled: false,
+            audit_log_enabled: false,
+            audit_log_path: String::new(),
+            audit_log_max_kib: 1024,
+            export_directory: String::new(),
+            history_panel_open: false,
+            history_panel_height: 120.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let Some(path) = settings_path() else {
+            return Self::default();
+        };
+        let mut settings: Settings = load_or_default(Some(path));
+        settings.clamp();
+        settings
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if memory_only() {
+            return Ok(());
+        }
+        let path = settings_path()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No config directory available"))?;
+        let contents = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        save_atomic(&path, contents.as_bytes())
+    }
+
+    // Move the current endpoint to the front of the recent list, minus any user:password@
+    pub fn remember_endpoint(&mut self) {
+        let Some(endpoint) = network::without_credentials(&self.endpoint) else {
+            return;
+        };
+        self.recent_endpoints.retain(|recent| *recent != endpoint);
+        self.recent_endpoints.insert(0, endpoint);
+        self.recent_endpoints.truncate(RECENT_ENDPOINTS_LIMIT);
+    }
+
+    // Keep hand-edited values inside the ranges the UI allows
+    pub fn clamp(&mut self) {
+        self.recent_endpoints = self.recent_endpoints.iter().filter_map(|endpoint| network::without_credentials(endpoint)).collect();
+        self.recent_endpoints.truncate(RECENT_ENDPOINTS_LIMIT);
+        self.window_opacity = self.window_opacity.clamp(MIN_WINDOW_OPACITY, 1.0);
+        self.idle_lock_seconds = self
+            .idle_lock_seconds
+            .clamp(*IDLE_LOCK_SECONDS_RANGE.start(), *IDLE_LOCK_SECONDS_RANGE.end());
+        self.history_limit = self.history_limit.min(*HISTORY_LIMIT_RANGE.end());
+        self.history_panel_height = self
+            .history_panel_height
+            .clamp(*HISTORY_PANEL_HEIGHT_RANGE.start(), *HISTORY_PANEL_HEIGHT_RANGE.end());
+        self.connect_timeout_seconds = self
+            .connect_timeout_seconds
+            .clamp(*TIMEOUT_SECONDS_RANGE.start(), *TIMEOUT_SECONDS_RANGE.end());
+        self.request_timeout_seconds = self
+            .request_timeout_seconds
+            .clamp(*TIMEOUT_SECONDS_RANGE.start(), *TIMEOUT_SECONDS_RANGE.end());
+        self.max_retries = self.max_retries.min(*MAX_RETRIES_RANGE.end());
+        self.retry_budget_seconds = self
+            .retry_budget_seconds
+            .clamp(*RETRY_BUDGET_SECONDS_RANGE.start(), *RETRY_BUDGET_SECONDS_RANGE.end());
+        self.max_response_body_kib = self
+            .max_response_body_kib
+            .clamp(*RESPONSE_BODY_KIB_RANGE.start(), *RESPONSE_BODY_KIB_RANGE.end());
+        // Anything but each field exactly once (hand-edited settings, say) goes back to the default
+        if self.field_order.len() != Field::ALL.len() || !Field::ALL.iter().all(|field| self.field_order.contains(field)) {
+            self.field_order = Field::ALL.to_vec();
+        }
+        self.audit_log_max_kib = self
+            .audit_log_max_kib
+            .clamp(*AUDIT_LOG_MAX_KIB_RANGE.start(), *AUDIT_LOG_MAX_KIB_RANGE.end());
+        self.http_method = network::parse_method(&self.http_method)
+            .map_or_else(|| "POST".to_string(), |method| method.to_string());
+        if self.endpoint.trim().is_empty() {
+            self.endpoint = DEFAULT_ENDPOINT.to_string();
+        }
+        for length in &mut self.fallback_image_size {
+            *length = length.clamp(*FALLBACK_IMAGE_SIZE_RANGE.start(), *FALLBACK_IMAGE_SIZE_RANGE.end());
+        }
+        self.animation_fps = self.animation_fps.clamp(*ANIMATION_FPS_RANGE.start(), *ANIMATION_FPS_RANGE.end());
+        self.submit_text_size = self
+            .submit_text_size
+            .clamp(*SUBMIT_TEXT_SIZE_RANGE.start(), *SUBMIT_TEXT_SIZE_RANGE.end());
+    }
+
+    // Resolved at draw time rather than in clamp(), which runs on every edit and would refill the
+    // field while it's being retyped
+    pub fn submit_label(&self) -> &str {
+        match self.submit_label.trim() {
+            "" => DEFAULT_SUBMIT_LABEL,
+            label => label,
+        }
+    }
+
+    pub fn submit_button_size(&self) -> egui::Vec2 {
+        egui::vec2(100.0, 25.0) * (self.submit_text_size / DEFAULT_SUBMIT_TEXT_SIZE)
+    }
+}
+
+// Settings and profiles files never get a proxy password, even one still being typed; the
+// settings window moves it to the keyring when the field loses focus
+fn serialize_proxy_url<S: serde::Serializer>(proxy_url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    match proxy_auth::split_password(proxy_url) {
+        Some((without_password, _)) => serializer.serialize_str(&without_password),
+        None => serializer.serialize_str(proxy_url),
+    }
+}
+
+// Black or white, whichever reads better on top of the given background colors
+pub fn contrasting_text_color(background: &[[u8; 3]]) -> [u8; 3] {
+    let luminance = background
+        .iter()
+        .map(|[r, g, b]| 0.2126 * *r as f32 + 0.7152 * *g as f32 + 0.0722 * *b as f32)
+        .sum::<f32>()
+        / background.len().max(1) as f32;
+    if luminance > 140.0 { [0, 0, 0] } else { [255, 255, 255] }
+}
+
+// Per-user configuration directory for this app, following each platform's convention
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|dir| dir.join(APP_DIR_NAME))
+}
+
+// Why the config directory can't be written to, decided once at startup by check_config_dir.
+// While set, every save is skipped and the app runs on what's in memory; reads still work.
+static MEMORY_ONLY_REASON: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Create the config directory and write a scratch file into it. On failure switch to memory-only
+// mode and return the reason, to be shown once.
+pub fn check_config_dir() -> Option<&'static str> {
+    let result = config_dir().ok_or_else(|| "no config directory available".to_string()).and_then(|dir| {
+        let probe = dir.join(".write-test");
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(&probe, b""))
+            .and_then(|_| std::fs::remove_file(&probe))
+            .map_err(|e| format!("can't write to {}: {}", dir.display(), e))
+    });
+    match result {
+        Ok(()) => None,
+        Err(reason) => Some(MEMORY_ONLY_REASON.get_or_init(|| reason)),
+    }
+}
+
+pub fn memory_only() -> bool {
+    MEMORY_ONLY_REASON.get().is_some()
+}
+
+// Every file the app keeps is written through here: the new contents go to "<name>.tmp" next to
+// the target and are renamed over it once they're on disk. Being killed part way leaves either
+// the old file or the new one, never half of either. A stale .tmp is simply replaced next time.
+// On Unix the directory is synced too, so the rename itself survives a power cut. The new file
+// is already in place by then, so a failed directory sync is only logged.
+pub fn save_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = write_temp(path, contents)?;
+    std::fs::rename(&temp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })?;
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if let Err(e) = std::fs::File::open(parent).and_then(|dir| dir.sync_all()) {
+            tracing::warn!("saved {} but couldn't sync its directory: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+// The first half of save_atomic, on its own so the self-test can stop between the two. On Unix
+// the file is readable by its owner only (it becomes the saved file, the draft among them).
+pub fn write_temp(path: &Path, contents: &[u8]) -> std::io::Result<PathBuf> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = temp_path(path);
+    let _ = std::fs::remove_file(&temp); // A leftover would keep its own permissions
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    std::io::Write::write_all(&mut file, contents)?;
+    file.sync_all()?;
+    Ok(temp)
+}
+
+// Files found unreadable this run, as "name (kept as name.bak)", until take_recovered_files
+static RECOVERED_FILES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Every JSON file the app keeps is read through here. A missing file means the default. One that
+// doesn't parse (cut short by a crash, say) is moved aside with a ".bak" suffix so it isn't
+// overwritten, and the default is used; the next save writes a good file again.
+pub fn load_or_default<T: DeserializeOwned + Default>(path: Option<PathBuf>) -> T {
+    let Some(path) = path else {
+        return T::default();
+    };
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(path = %path.display(), "couldn't read: {}", e);
+            }
+            return T::default();
+        }
+    };
+    serde_json::from_slice(&contents).unwrap_or_else(|e| {
+        set_aside_corrupt(&path, &e.to_string());
+        T::default()
+    })
+}
+
+// Move an unreadable file to "<name>.bak" (replacing an older one), log why and note it for the
+// start-up notice. For files that aren't plain JSON, like the encrypted draft.
+pub fn set_aside_corrupt(path: &Path, reason: &str) {
+    let backup = backup_path(path);
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+    match std::fs::rename(path, &backup) {
+        Ok(()) => {
+            tracing::warn!(path = %path.display(), backup = %backup.display(), "unreadable ({}); set aside and started from defaults", reason);
+            let backup_name = backup.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            RECOVERED_FILES.lock().unwrap().push(format!("{} (kept as {})", name, backup_name));
+        }
+        Err(e) => {
+            tracing::warn!(path = %path.display(), "unreadable ({}) and couldn't be set aside: {}", reason, e);
+            RECOVERED_FILES.lock().unwrap().push(name);
+        }
+    }
+}
+
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+// The files recovered so far, each reported once
+pub fn take_recovered_files() -> Vec<String> {
+    std::mem::take(&mut *RECOVERED_FILES.lock().unwrap())
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(SETTINGS_FILE_NAME))
+}
@@ -0,0 +1,77 @@
+// This module contains optional request signing. The Ed25519 key lives in the OS keyring; with
+// signing on, each HTTP body is signed exactly as sent and the signature and public key travel
+// as headers, so the server can check the request came from this install and wasn't altered.
+// WebSocket frames have no headers and are never signed.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use zeroize::Zeroize;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature"; // Base64 Ed25519 signature of the raw body
+pub const PUBLIC_KEY_HEADER: &str = "X-Signature-Key"; // Base64 public key to verify it with
+const KEYRING_SERVICE: &str = "creditcard";
+const KEYRING_USER: &str = "request-signing-key";
+
+fn entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| format!("No secure key store available: {}", e))
+}
+
+// The stored key, or None when none has been generated yet
+pub fn load() -> Result<Option<SigningKey>, String> {
+    let mut secret = match entry()?.get_secret() {
+        Ok(secret) => secret,
+        Err(keyring::Error::NoEntry) => return Ok(None),
+        Err(e) => return Err(format!("Couldn't read the signing key: {}", e)),
+    };
+    let key = <[u8; ed25519_dalek::SECRET_KEY_LENGTH]>::try_from(secret.as_slice())
+        .map(|mut seed| {
+            let key = SigningKey::from_bytes(&seed);
+            seed.zeroize();
+            key
+        })
+        .map_err(|_| "The stored signing key is corrupt".to_string());
+    secret.zeroize();
+    key.map(Some)
+}
+
+// Make a new key and store it, replacing any earlier one
+pub fn generate() -> Result<SigningKey, String> {
+    let mut seed = [0; ed25519_dalek::SECRET_KEY_LENGTH];
+    SystemRandom::new().fill(&mut seed).map_err(|_| "Couldn't generate a signing key".to_string())?;
+    let key = SigningKey::from_bytes(&seed);
+    let stored = entry()?.set_secret(&seed).map_err(|e| format!("Couldn't store the signing key: {}", e));
+    seed.zeroize();
+    stored.map(|()| key)
+}
+
+pub fn remove() -> Result<(), String> {
+    match entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Couldn't remove the signing key: {}", e)),
+    }
+}
+
+pub fn public_key(key: &VerifyingKey) -> String {
+    BASE64.encode(key.as_bytes())
+}
+
+// The two headers to add to a request with this body
+pub fn headers(key: &SigningKey, body: &[u8]) -> [(&'static str, String); 2] {
+    [
+        (SIGNATURE_HEADER, BASE64.encode(key.sign(body).to_bytes())),
+        (PUBLIC_KEY_HEADER, public_key(&key.verifying_key())),
+    ]
+}
+
+// What a server would do with those headers
+pub fn verify(public_key: &str, signature: &str, body: &[u8]) -> Result<(), String> {
+    let public_key = BASE64.decode(public_key).map_err(|e| format!("Public key isn't base64: {}", e))?;
+    let public_key = <[u8; ed25519_dalek::PUBLIC_KEY_LENGTH]>::try_from(public_key.as_slice())
+        .map_err(|_| "Public key has the wrong length".to_string())?;
+    let public_key = VerifyingKey::from_bytes(&public_key).map_err(|e| format!("Invalid public key: {}", e))?;
+    let signature = BASE64.decode(signature).map_err(|e| format!("Signature isn't base64: {}", e))?;
+    let signature = Signature::from_slice(&signature).map_err(|e| format!("Invalid signature: {}", e))?;
+    public_key.verify(body, &signature).map_err(|_| "Signature doesn't match the body".to_string())
+}
@@ -0,0 +1,98 @@
+// This module contains the endpoint health sweep: a bare HEAD request to every endpoint the
+// user knows about (the current one, the remembered ones and the profiles'), a few at a time,
+// timing each. No card data is sent. Results live in memory for the sweep window.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use tokio::task::JoinSet;
+
+use crate::network::{self, SendConfig};
+use crate::profiles::ProfileStore;
+use crate::settings::Settings;
+
+const CONCURRENCY: usize = 4; // Probes in flight at once
+
+#[derive(Clone, Copy, Debug)]
+pub struct Probe {
+    pub latency_ms: u64,
+    pub status: u16, // Any HTTP answer counts as reachable
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepRow {
+    pub endpoint: String,
+    pub result: Option<Result<Probe, String>>, // None until its probe finishes
+}
+
+pub struct Sweep {
+    pub rows: Arc<Mutex<Vec<SweepRow>>>,
+}
+
+impl Sweep {
+    // The reachable endpoint with the lowest latency, once it's been probed
+    pub fn fastest(rows: &[SweepRow]) -> Option<usize> {
+        rows.iter()
+            .enumerate()
+            .filter_map(|(index, row)| Some((index, row.result.as_ref()?.as_ref().ok()?.latency_ms)))
+            .min_by_key(|&(_, latency_ms)| latency_ms)
+            .map(|(index, _)| index)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.rows.lock().unwrap().iter().all(|row| row.result.is_some())
+    }
+}
+
+// Every HTTP endpoint in the settings and profiles, current one first, without repeats
+pub fn known_endpoints(settings: &Settings, profiles: &ProfileStore) -> Vec<String> {
+    let mut endpoints: Vec<String> = Vec::new();
+    let candidates = std::iter::once(&settings.endpoint)
+        .chain(&settings.recent_endpoints)
+        .chain(profiles.profiles.iter().map(|profile| &profile.settings.endpoint));
+    for endpoint in candidates {
+        let endpoint = endpoint.trim();
+        let is_http = endpoint.starts_with("http://") || endpoint.starts_with("https://");
+        if is_http && !endpoints.iter().any(|known| known == endpoint) {
+            endpoints.push(endpoint.to_string());
+        }
+    }
+    endpoints
+}
+
+// Probe with the current timeouts and proxy, at most CONCURRENCY at a time, filling in each
+// row as its answer comes back
+pub fn start(endpoints: Vec<String>, config: SendConfig, ctx: egui::Context) -> Sweep {
+    let rows: Vec<SweepRow> = endpoints.iter().map(|endpoint| SweepRow { endpoint: endpoint.clone(), result: None }).collect();
+    let rows = Arc::new(Mutex::new(rows));
+    let results = rows.clone();
+    tokio::spawn(async move {
+        let mut pending = endpoints.into_iter().enumerate();
+        let mut probes = JoinSet::new();
+        let mut rows_by_task = HashMap::new(); // A probe that panics only comes back as its task id
+        loop {
+            while probes.len() < CONCURRENCY {
+                let Some((index, endpoint)) = pending.next() else {
+                    break;
+                };
+                let config = SendConfig { endpoint, ..config.clone() };
+                let task = probes.spawn(async move { network::probe_endpoint(&config).await });
+                rows_by_task.insert(task.id(), index);
+            }
+            let Some(joined) = probes.join_next_with_id().await else {
+                break;
+            };
+            let (index, result) = match joined {
+                Ok((id, result)) => (
+                    rows_by_task[&id],
+                    result.map(|(latency, status)| Probe { latency_ms: latency.as_millis() as u64, status: status.as_u16() }),
+                ),
+                Err(e) => (rows_by_task[&e.id()], Err(format!("Probe didn't finish: {}", e))),
+            };
+            results.lock().unwrap()[index].result = Some(result);
+            ctx.request_repaint();
+        }
+    });
+    Sweep { rows }
+}
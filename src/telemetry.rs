@@ -0,0 +1,88 @@
+// This module contains the opt-in usage stats: how many cards were sent, how many went through
+// and how long sends took. They're only ever written to a local file for the user to look at;
+// nothing here is transmitted and no card values are recorded.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings;
+
+const TELEMETRY_FILE_NAME: &str = "telemetry.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Stats {
+    pub since: Option<chrono::DateTime<chrono::Local>>, // First send recorded since the last clear
+    pub submissions: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub total_latency_ms: u64,
+}
+
+impl Stats {
+    pub fn average_latency_ms(&self) -> Option<u64> {
+        (self.submissions > 0).then(|| self.total_latency_ms / self.submissions)
+    }
+}
+
+pub struct Telemetry {
+    stats: Stats,
+    enabled: bool,
+}
+
+impl Telemetry {
+    // Saved stats are shown even while recording is off, so turning it off doesn't lose them
+    pub fn load(enabled: bool) -> Self {
+        let stats = settings::load_or_default(telemetry_path());
+        Self { stats, enabled }
+    }
+
+    pub fn configure(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn record(&mut self, succeeded: bool, latency_ms: u64) {
+        if !self.enabled {
+            return;
+        }
+        let stats = &mut self.stats;
+        stats.since.get_or_insert_with(chrono::Local::now);
+        stats.submissions += 1;
+        if succeeded {
+            stats.successes += 1;
+        } else {
+            stats.failures += 1;
+        }
+        stats.total_latency_ms += latency_ms;
+        self.save();
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn clear(&mut self) {
+        self.stats = Stats::default();
+        if let Some(path) = telemetry_path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    fn save(&self) {
+        if settings::memory_only() {
+            return;
+        }
+        let Some(path) = telemetry_path() else {
+            return;
+        };
+        let result = settings::save_atomic(&path, serde_json::to_string_pretty(&self.stats).unwrap_or_default().as_bytes());
+        if let Err(e) = result {
+            tracing::warn!("failed to save telemetry: {}", e);
+        }
+    }
+}
+
+fn telemetry_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(TELEMETRY_FILE_NAME))
+}
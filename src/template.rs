@@ -0,0 +1,60 @@
+// This module contains the user-editable status message templates, e.g.
+// "Sent card ending in {last4} ({ms} ms)". `{{` and `}}` produce literal braces; placeholders
+// nobody provides a value for are kept as typed.
+
+pub const DEFAULT_SUCCESS_TEMPLATE: &str = "Successfully sent card info!";
+pub const DEFAULT_ERROR_TEMPLATE: &str = "Error: {error}";
+pub const PLACEHOLDERS: &[&str] = &["last4", "status", "id", "ms", "error"]; // Listed in the settings hint
+
+enum Piece<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse(template: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        pieces.push(Piece::Text(&rest[..index]));
+        let brace = &rest[index..index + 1];
+        rest = &rest[index + 1..];
+        if rest.starts_with(brace) {
+            pieces.push(Piece::Text(brace));
+            rest = &rest[1..];
+        } else if brace == "}" {
+            return Err("Unmatched \"}\" (write \"}}\" for a literal brace)".to_string());
+        } else {
+            let end = rest.find('}').ok_or_else(|| "Unclosed \"{\"".to_string())?;
+            let name = &rest[..end];
+            if name.contains('{') {
+                return Err("Unclosed \"{\"".to_string());
+            }
+            pieces.push(Piece::Placeholder(name.trim()));
+            rest = &rest[end + 1..];
+        }
+    }
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+pub fn validate(template: &str) -> Result<(), String> {
+    parse(template).map(|_| ())
+}
+
+// Fill in a template, using `fallback` instead if it doesn't parse or is blank
+pub fn render(template: &str, fallback: &str, values: &[(&str, String)]) -> String {
+    let pieces = match parse(template) {
+        Ok(pieces) if !template.trim().is_empty() => pieces,
+        _ => parse(fallback).unwrap_or_default(),
+    };
+    pieces
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.to_string(),
+            Piece::Placeholder(name) => values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map_or_else(|| format!("{{{}}}", name), |(_, value)| value.clone()),
+        })
+        .collect()
+}
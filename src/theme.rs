@@ -0,0 +1,244 @@
+// This module contains the light and dark color themes, following the OS preference when asked
+// to, and the short blend between them when the user switches
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use eframe::egui::{self, Color32};
+use serde::{Deserialize, Serialize};
+
+use crate::animation;
+use crate::validation::CardNetwork;
+
+const TRANSITION_SECONDS: f32 = 0.2;
+const SYSTEM_THEME_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Theme {
+    #[default]
+    Light, // Classic Windows 7 dialog look
+    Dark,
+    System, // Light or Dark to match the OS, see SystemTheme
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Light, Theme::Dark, Theme::System];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+            Theme::System => "Match system",
+        }
+    }
+
+    // System is resolved to Light or Dark before anything is painted; it only lands here
+    // if the OS preference is unknown
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Light | Theme::System => Palette {
+                text: Color32::BLACK,
+                weak_text: Color32::GRAY,
+                panel_fill: Color32::from_rgb(240, 240, 240),
+                side_panel_fill: Color32::from_rgb(232, 232, 232),
+                field_fill: Color32::WHITE,
+                widget_fill: Color32::from_gray(230),
+                widget_hovered: Color32::from_gray(210),
+                widget_active: Color32::from_gray(200),
+                widget_open: Color32::from_gray(220),
+                border: Color32::from_gray(150),
+                border_strong: Color32::from_gray(100),
+                title_bar_shade: 0.0,
+                dark: 0.0,
+            },
+            Theme::Dark => Palette {
+                text: Color32::from_gray(225),
+                weak_text: Color32::from_gray(140),
+                panel_fill: Color32::from_rgb(38, 40, 44),
+                side_panel_fill: Color32::from_rgb(32, 34, 37),
+                field_fill: Color32::from_gray(24),
+                widget_fill: Color32::from_gray(60),
+                widget_hovered: Color32::from_gray(75),
+                widget_active: Color32::from_gray(90),
+                widget_open: Color32::from_gray(68),
+                border: Color32::from_gray(90),
+                border_strong: Color32::from_gray(130),
+                title_bar_shade: 0.65,
+                dark: 1.0,
+            },
+        }
+    }
+}
+
+// The colors the UI is painted with; a blend of two of these is one too
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub text: Color32,
+    pub weak_text: Color32,
+    pub panel_fill: Color32,
+    pub side_panel_fill: Color32, // History panel
+    pub field_fill: Color32, // Text boxes and the receipt card
+    pub widget_fill: Color32,
+    pub widget_hovered: Color32,
+    pub widget_active: Color32,
+    pub widget_open: Color32,
+    pub border: Color32,
+    pub border_strong: Color32, // Hovered and pressed widgets
+    pub title_bar_shade: f32, // How far the user's title bar colors are pulled toward black
+    pub dark: f32, // 0 light .. 1 dark, for egui's own defaults
+}
+
+impl Palette {
+    pub fn lerp(&self, other: &Palette, t: f32) -> Palette {
+        let mix = |a: Color32, b: Color32| a.lerp_to_gamma(b, t);
+        Palette {
+            text: mix(self.text, other.text),
+            weak_text: mix(self.weak_text, other.weak_text),
+            panel_fill: mix(self.panel_fill, other.panel_fill),
+            side_panel_fill: mix(self.side_panel_fill, other.side_panel_fill),
+            field_fill: mix(self.field_fill, other.field_fill),
+            widget_fill: mix(self.widget_fill, other.widget_fill),
+            widget_hovered: mix(self.widget_hovered, other.widget_hovered),
+            widget_active: mix(self.widget_active, other.widget_active),
+            widget_open: mix(self.widget_open, other.widget_open),
+            border: mix(self.border, other.border),
+            border_strong: mix(self.border_strong, other.border_strong),
+            title_bar_shade: egui::lerp(self.title_bar_shade..=other.title_bar_shade, t),
+            dark: egui::lerp(self.dark..=other.dark, t),
+        }
+    }
+
+    // A title bar color from the settings, adjusted for this palette
+    pub fn title_bar_color(&self, [r, g, b]: [u8; 3]) -> Color32 {
+        Color32::from_rgb(r, g, b).lerp_to_gamma(Color32::from_gray(20), self.title_bar_shade)
+    }
+
+    pub fn visuals(&self) -> egui::Visuals {
+        let widget = |fill: Color32, border: Color32, text: Color32| egui::style::WidgetVisuals {
+            bg_fill: fill,
+            weak_bg_fill: fill,
+            bg_stroke: egui::Stroke::new(1.0, border),
+            fg_stroke: egui::Stroke::new(1.0, text),
+            corner_radius: egui::CornerRadius::same(2),
+            expansion: 0.0,
+        };
+        let base = if self.dark < 0.5 { egui::Visuals::light() } else { egui::Visuals::dark() };
+
+        egui::Visuals {
+            dark_mode: self.dark >= 0.5,
+            window_corner_radius: egui::CornerRadius::ZERO,
+            window_shadow: egui::epaint::Shadow::NONE, // No shadow
+            window_fill: self.panel_fill,
+            panel_fill: self.panel_fill,
+            extreme_bg_color: self.field_fill,
+            override_text_color: Some(self.text),
+            // Windows 7 focus look: a crisp blue border on the focused field (see also paint_focus_glow)
+            selection: egui::style::Selection {
+                bg_fill: Color32::from_rgb(173, 214, 255).lerp_to_gamma(Color32::from_rgb(38, 79, 120), self.dark),
+                stroke: egui::Stroke::new(1.0, Color32::from_rgb(51, 153, 255)),
+            },
+            widgets: egui::style::Widgets {
+                inactive: widget(self.widget_fill, self.border, self.text),
+                hovered: widget(self.widget_hovered, self.border_strong, self.text),
+                active: widget(self.widget_active, self.border_strong, self.text),
+                open: widget(self.widget_open, self.border_strong, self.text),
+                noninteractive: widget(self.widget_fill, self.border, self.weak_text),
+            },
+            ..base
+        }
+    }
+}
+
+// The OS preference right now. Unknown (no desktop portal, an older OS) counts as light.
+pub fn detect_system_theme() -> Theme {
+    match dark_light::detect() {
+        Ok(dark_light::Mode::Dark) => Theme::Dark,
+        _ => Theme::Light,
+    }
+}
+
+// Follows the OS preference while the System theme is selected. A background thread checks it
+// every few seconds and repaints on a change; it stops once another theme is picked.
+#[derive(Default)]
+pub struct SystemTheme {
+    dark: Option<Arc<AtomicBool>>, // Written by the polling thread, which holds a weak reference
+}
+
+impl SystemTheme {
+    // The theme to actually paint: `theme` itself, or the OS's choice for System
+    pub fn resolve(&mut self, ctx: &egui::Context, theme: Theme) -> Theme {
+        if theme != Theme::System {
+            self.dark = None;
+            return theme;
+        }
+        let dark = self.dark.get_or_insert_with(|| watch_system_theme(ctx.clone()));
+        if dark.load(Ordering::Relaxed) {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+}
+
+fn watch_system_theme(ctx: egui::Context) -> Arc<AtomicBool> {
+    let dark = Arc::new(AtomicBool::new(detect_system_theme() == Theme::Dark));
+    let watched = Arc::downgrade(&dark);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SYSTEM_THEME_POLL_INTERVAL);
+        let Some(dark) = watched.upgrade() else {
+            break;
+        };
+        let now = detect_system_theme() == Theme::Dark;
+        if dark.swap(now, Ordering::Relaxed) != now {
+            ctx.request_repaint();
+        }
+    });
+    dark
+}
+
+// Blend from whatever was on screen to the selected theme's palette
+pub struct Transition {
+    from: Palette,
+    target: Theme,
+    progress: f32, // 0..=1
+}
+
+impl Transition {
+    pub fn new(theme: Theme) -> Self {
+        Self { from: theme.palette(), target: theme, progress: 1.0 }
+    }
+
+    // The palette for this frame, repainting at `fps` until the blend has finished
+    pub fn update(&mut self, ctx: &egui::Context, theme: Theme, animate: bool, fps: u32) -> Palette {
+        if theme != self.target {
+            self.from = self.current();
+            self.target = theme;
+            self.progress = if animate { 0.0 } else { 1.0 };
+        }
+        if self.progress < 1.0 {
+            let dt = ctx.input(|i| i.stable_dt).min(0.1);
+            self.progress = (self.progress + dt / TRANSITION_SECONDS).min(1.0);
+            animation::request_frame(ctx, fps);
+        }
+        self.current()
+    }
+
+    fn current(&self) -> Palette {
+        self.from.lerp(&self.target.palette(), self.progress)
+    }
+}
+
+// Accent for the detected card brand, each a slightly deepened take on the brand's own color
+// so white text on it stays above a 4.5:1 contrast ratio. None keeps the theme's colors.
+pub fn brand_color(network: Option<CardNetwork>) -> Option<Color32> {
+    Some(match network? {
+        CardNetwork::Visa => Color32::from_rgb(26, 31, 113),
+        CardNetwork::Mastercard => Color32::from_rgb(235, 0, 27),
+        CardNetwork::Amex => Color32::from_rgb(0, 111, 207),
+        CardNetwork::Discover => Color32::from_rgb(194, 78, 0),
+        CardNetwork::Jcb => Color32::from_rgb(11, 78, 162),
+        CardNetwork::DinersClub => Color32::from_rgb(0, 121, 190),
+        CardNetwork::UnionPay => Color32::from_rgb(209, 4, 41),
+    })
+}
@@ -0,0 +1,131 @@
+// This module contains sending over a Unix domain socket, for endpoints like
+// unix:///tmp/card.sock used in local integration tests. The request is the same HTTP/1.1 the
+// normal path sends, made with hyper directly since reqwest can't dial a socket path.
+
+use crate::network::{SendConfig, SendError, SendSuccess};
+use crate::CardInfo;
+
+const UNIX_SCHEME: &str = "unix://";
+
+pub fn is_unix_endpoint(endpoint: &str) -> bool {
+    endpoint.starts_with(UNIX_SCHEME)
+}
+
+fn socket_path(config: &SendConfig) -> &str {
+    &config.endpoint[UNIX_SCHEME.len()..]
+}
+
+// The request post_card_info sends for `body`, shared with the raw request review
+#[cfg(unix)]
+fn build_request(config: &SendConfig, body: Vec<u8>) -> Result<hyper::Request<http_body_util::Full<bytes::Bytes>>, String> {
+    let mut request = hyper::Request::builder()
+        .method(config.method.clone())
+        .uri("/")
+        .header(hyper::header::HOST, "localhost")
+        .header(hyper::header::CONTENT_TYPE, config.body_format.content_type());
+    if let Some(key) = &config.idempotency_key {
+        request = request.header(crate::network::IDEMPOTENCY_KEY_HEADER, key);
+    }
+    if let Some(key) = &config.signing_key {
+        for (name, value) in crate::signing::headers(key, &body) {
+            request = request.header(name, value);
+        }
+    }
+    request
+        .body(http_body_util::Full::new(bytes::Bytes::from(body)))
+        .map_err(|e| format!("Couldn't build the request: {}", e))
+}
+
+// Request line and headers as they go over the socket, for the raw request review
+#[cfg(unix)]
+pub fn describe_request(config: &SendConfig, body: &[u8]) -> Result<String, String> {
+    let request = build_request(config, body.to_vec())?;
+    let mut text = format!("{} {} HTTP/1.1\n", request.method(), request.uri());
+    for (name, value) in request.headers() {
+        text.push_str(&format!("{}: {}\n", name, value.to_str().unwrap_or("<binary>")));
+    }
+    text.push_str(&format!("(Content-Length is added when the request is sent, over the socket {})\n\n", socket_path(config)));
+    Ok(text)
+}
+
+#[cfg(unix)]
+pub async fn post_card_info(config: &SendConfig, card_info: &CardInfo) -> Result<SendSuccess, SendError> {
+    use hyper_util::rt::TokioIo;
+
+    use crate::network;
+
+    let path = socket_path(config);
+    let request = build_request(config, config.body_format.encode(card_info)?)?;
+
+    let exchange = async {
+        let stream = match tokio::time::timeout(config.timeouts.connect, tokio::net::UnixStream::connect(path)).await {
+            Ok(stream) => stream.map_err(|e| SendError::unreachable(format!("Failed to send request: couldn't connect to {}: {}", path, e)))?,
+            Err(_) => {
+                return Err(SendError::timeout(format!(
+                    "Failed to send request: couldn't connect within {} s (connect timeout); is anything listening on {}?",
+                    config.timeouts.connect.as_secs(),
+                    path
+                )))
+            }
+        };
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| SendError::unreachable(format!("Failed to send request: {}", e)))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!("Unix socket connection error: {}", e);
+            }
+        });
+
+        let res = sender.send_request(request).await.map_err(|e| SendError::unreachable(format!("Failed to send request: {}", e)))?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        Ok((status, headers, read_body(res.into_body(), config.max_response_body).await))
+    };
+
+    let (status, headers, body) = match tokio::time::timeout(config.timeouts.total, exchange).await {
+        Ok(result) => result?,
+        Err(_) => {
+            return Err(SendError::timeout(format!(
+                "Failed to send request: no response within {} s (request timeout); the server is slow to respond",
+                config.timeouts.total.as_secs()
+            )))
+        }
+    };
+    if status.is_success() {
+        Ok(SendSuccess {
+            transaction_id: body.and_then(|body| config.body_format.transaction_id(&body.bytes)),
+            status: Some(status.as_u16()),
+            connection: None,
+            headers: network::response_headers(&headers),
+        })
+    } else {
+        Err(network::status_error(status, &headers, body.as_ref()))
+    }
+}
+
+// Frame by frame so a huge body is cut off at the limit rather than read in whole
+#[cfg(unix)]
+async fn read_body(mut body: hyper::body::Incoming, limit: usize) -> Option<crate::network::ResponseBody> {
+    use http_body_util::BodyExt;
+
+    let mut read = crate::network::ResponseBody::default();
+    while let Some(frame) = body.frame().await {
+        if let Some(chunk) = frame.ok()?.data_ref() {
+            if !read.push(chunk, limit) {
+                break;
+            }
+        }
+    }
+    Some(read)
+}
+
+#[cfg(not(unix))]
+pub fn describe_request(_config: &SendConfig, _body: &[u8]) -> Result<String, String> {
+    Err("unix:// endpoints are only supported on Unix-like systems".to_string())
+}
+
+#[cfg(not(unix))]
+pub async fn post_card_info(_config: &SendConfig, _card_info: &CardInfo) -> Result<SendSuccess, SendError> {
+    Err("Failed to send request: unix:// endpoints are only supported on Unix-like systems".to_string().into())
+}
@@ -0,0 +1,540 @@
+// This module contains the validation helpers for the card form
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::CardInfo;
+
+// User-facing strings live here so they can be swapped out for translations in one place
+const MISSING_PREFIX: &str = "Missing";
+const INVALID_PREFIX: &str = "Invalid";
+const EXPIRED_PREFIX: &str = "Expired";
+const EXPIRES_SOON_TEXT: &str = "Card expires within a month";
+const UNKNOWN_NETWORK_TEXT: &str = "Card network not recognised";
+const TEST_NUMBER_TEXT: &str = "This is a well-known test card number";
+const REPEATED_DIGITS_TEXT: &str = "Every digit is the same";
+const SEQUENTIAL_DIGITS_TEXT: &str = "The digits just count up or down";
+const PATTERN_MISMATCH_PREFIX: &str = "Doesn't match its pattern";
+
+const NEAR_EXPIRY_MONTHS: u32 = 1; // Cards expiring this many months from now (or sooner) get a warning
+
+// Publicly documented test numbers that pass Luhn but are never real accounts
+pub const TEST_CARD_NUMBERS: &[&str] = &[
+    "4111111111111111", // Visa
+    "4242424242424242", // Visa
+    "5555555555554444", // Mastercard
+    "378282246310005",  // American Express
+    "6011111111111117", // Discover
+    "3530111333300000", // JCB
+];
+
+// When a field's inline feedback (the red outline) shows up; submitting always validates
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ValidationTiming {
+    #[default]
+    OnBlur,
+    OnChange,
+}
+
+impl ValidationTiming {
+    pub const ALL: [ValidationTiming; 2] = [ValidationTiming::OnBlur, ValidationTiming::OnChange];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ValidationTiming::OnBlur => "When leaving a field",
+            ValidationTiming::OnChange => "While typing",
+        }
+    }
+}
+
+// How the expiry year is written in the CardInfo that gets sent
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ExpiryFormat {
+    #[default]
+    TwoDigitYear,
+    FourDigitYear,
+}
+
+impl ExpiryFormat {
+    pub const ALL: [ExpiryFormat; 2] = [ExpiryFormat::TwoDigitYear, ExpiryFormat::FourDigitYear];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExpiryFormat::TwoDigitYear => "MM/YY",
+            ExpiryFormat::FourDigitYear => "MM/YYYY",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Field {
+    CardNumber,
+    CardholderName,
+    ExpiryDate,
+    SecurityCode,
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [Field::CardNumber, Field::CardholderName, Field::ExpiryDate, Field::SecurityCode];
+
+    // This field's entry out of one value per field, given in Field::ALL order. Shared by the
+    // structs that keep a string per field (CardInfo, FieldPatterns).
+    pub fn pick<T>(self, [card_number, cardholder_name, expiry_date, security_code]: [T; 4]) -> T {
+        match self {
+            Field::CardNumber => card_number,
+            Field::CardholderName => cardholder_name,
+            Field::ExpiryDate => expiry_date,
+            Field::SecurityCode => security_code,
+        }
+    }
+
+    // Longest value kept, in characters. Generous for typing and formatting, but small enough
+    // that pasting a huge blob doesn't end up in memory or in the request.
+    pub fn max_len(self) -> usize {
+        match self {
+            Field::CardNumber => 32,
+            Field::CardholderName => 128,
+            Field::ExpiryDate => 16,
+            Field::SecurityCode => 8,
+        }
+    }
+
+    // Label shown next to the input in the form grid
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::CardNumber => "Card number",
+            Field::CardholderName => "Name on card",
+            Field::ExpiryDate => "Expiry date",
+            Field::SecurityCode => "Security code",
+        }
+    }
+
+    // Short name used when listing problems in the status area
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Field::CardNumber => "card number",
+            Field::CardholderName => "name",
+            Field::ExpiryDate => "expiry",
+            Field::SecurityCode => "CVV",
+        }
+    }
+}
+
+// Cut a value down to `max_len` characters, wiping the old buffer. Returns whether anything was cut.
+pub fn cap_length(value: &mut String, max_len: usize) -> bool {
+    let Some((cut_at, _)) = value.char_indices().nth(max_len) else {
+        return false;
+    };
+    let mut old = std::mem::replace(value, value[..cut_at].to_string());
+    old.zeroize();
+    true
+}
+
+// Apply every field's cap; returns the fields that were cut
+pub fn cap_card_info(card_info: &mut CardInfo) -> Vec<Field> {
+    Field::ALL.into_iter().filter(|&field| cap_length(card_info.field_mut(field), field.max_len())).collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Problem {
+    Missing,
+    Invalid,
+    Expired,
+    ExpiresSoon,
+    UnknownNetwork,
+    Placeholder(&'static str), // Carries the reason from placeholder_reason
+    PatternMismatch, // Fails the custom regex from the settings (see patterns.rs)
+}
+
+impl Problem {
+    pub fn label(self) -> &'static str {
+        match self {
+            Problem::Missing => MISSING_PREFIX,
+            Problem::Invalid => INVALID_PREFIX,
+            Problem::Expired => EXPIRED_PREFIX,
+            Problem::ExpiresSoon => EXPIRES_SOON_TEXT,
+            Problem::UnknownNetwork => UNKNOWN_NETWORK_TEXT,
+            Problem::Placeholder(reason) => reason,
+            Problem::PatternMismatch => PATTERN_MISMATCH_PREFIX,
+        }
+    }
+
+    // Soft warnings can be bypassed with "Submit anyway"; everything else is a hard block
+    pub fn overridable(self) -> bool {
+        matches!(self, Problem::ExpiresSoon | Problem::UnknownNetwork | Problem::Placeholder(_))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CardNetwork {
+    Visa,
+    Mastercard,
+    Amex,
+    Discover,
+    Jcb,
+    DinersClub,
+    UnionPay,
+}
+
+impl CardNetwork {
+    pub fn name(self) -> &'static str {
+        match self {
+            CardNetwork::Visa => "Visa",
+            CardNetwork::Mastercard => "Mastercard",
+            CardNetwork::Amex => "American Express",
+            CardNetwork::Discover => "Discover",
+            CardNetwork::Jcb => "JCB",
+            CardNetwork::DinersClub => "Diners Club",
+            CardNetwork::UnionPay => "UnionPay",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FieldIssue {
+    pub field: Field,
+    pub problem: Problem,
+}
+
+// Strip the separators people commonly type between digit groups
+pub fn card_digits(card_number: &str) -> String {
+    card_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect()
+}
+
+// How a network prints its numbers, e.g. Amex "3782 822463 10005"; digits past the last
+// group carry on in fours
+fn digit_groups(network: Option<CardNetwork>, digit_count: usize) -> &'static [usize] {
+    match network {
+        Some(CardNetwork::Amex) => &[4, 6, 5],
+        Some(CardNetwork::DinersClub) if digit_count == 14 => &[4, 6, 4],
+        _ => &[4, 4, 4, 4],
+    }
+}
+
+// Regroup the digits for display. Idempotent, and anything that isn't just digits and
+// separators is left alone so the user's input is never mangled.
+pub fn format_card_number(card_number: &str) -> String {
+    let digits = strip_card_formatting(card_number);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return card_number.to_string();
+    }
+
+    let groups = digit_groups(detect_network(&digits), digits.len());
+    let mut sizes = groups.iter().copied().chain(std::iter::repeat(4));
+    let mut formatted = String::with_capacity(digits.len() + 5);
+    let mut rest = digits.as_str();
+    while !rest.is_empty() {
+        let size = sizes.next().unwrap_or(4).min(rest.len());
+        if !formatted.is_empty() {
+            formatted.push(' ');
+        }
+        formatted.push_str(&rest[..size]);
+        rest = &rest[size..];
+    }
+    formatted
+}
+
+pub fn strip_card_formatting(card_number: &str) -> String {
+    card_digits(card_number)
+}
+
+// The card number as it goes in the request body: digits only unless the user asked to send
+// it exactly as it reads in the field
+pub fn payload_card_number(card_number: &str, keep_formatting: bool) -> String {
+    if keep_formatting {
+        card_number.trim().to_string()
+    } else {
+        strip_card_formatting(card_number)
+    }
+}
+
+// Everything but the last four digits replaced, e.g. "•••• 1111"
+pub fn masked_card_number(card_number: &str) -> String {
+    format!("•••• {}", last_four(card_number))
+}
+
+// The last four digits, or XXXX for a number too short to have more than those
+pub fn last_four(card_number: &str) -> String {
+    // By chars, since the field can hold anything the user typed
+    let digits: Vec<char> = card_digits(card_number).chars().collect();
+    if digits.len() > 4 { digits[digits.len() - 4..].iter().collect() } else { "XXXX".to_string() }
+}
+
+// Shareable one-line description, e.g. "Visa •••• 1111, exp 12/27". Never includes the CVV,
+// the full number or the cardholder name.
+pub fn masked_summary(card_info: &CardInfo) -> String {
+    let brand = detect_network(&card_digits(&card_info.card_number)).map_or("Card", CardNetwork::name);
+    let mut summary = format!("{} {}", brand, masked_card_number(&card_info.card_number));
+    if let Some((month, year)) = parse_expiry(&card_info.expiry_date) {
+        summary.push_str(&format!(", exp {:02}/{:02}", month, year % 100));
+    }
+    summary
+}
+
+// Identify the card network from the leading digits (IIN ranges)
+pub fn detect_network(digits: &str) -> Option<CardNetwork> {
+    let prefix = |len: usize| -> Option<u32> { digits.get(..len).and_then(|p| p.parse().ok()) };
+    let in_range = |len: usize, low: u32, high: u32| prefix(len).is_some_and(|p| (low..=high).contains(&p));
+
+    if digits.starts_with('4') {
+        Some(CardNetwork::Visa)
+    } else if in_range(2, 51, 55) || in_range(4, 2221, 2720) {
+        Some(CardNetwork::Mastercard)
+    } else if in_range(2, 34, 34) || in_range(2, 37, 37) {
+        Some(CardNetwork::Amex)
+    } else if digits.starts_with("6011") || in_range(2, 65, 65) || in_range(3, 644, 649) {
+        Some(CardNetwork::Discover)
+    } else if in_range(4, 3528, 3589) {
+        Some(CardNetwork::Jcb)
+    } else if in_range(3, 300, 305) || in_range(2, 36, 36) || in_range(2, 38, 39) {
+        Some(CardNetwork::DinersClub)
+    } else if in_range(2, 62, 62) {
+        Some(CardNetwork::UnionPay)
+    } else {
+        None
+    }
+}
+
+// Standard Luhn checksum over a string of ASCII digits
+pub fn luhn_valid(digits: &str) -> bool {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let digit = (b - b'0') as u32;
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+pub fn check_card_number(card_number: &str) -> Option<Problem> {
+    let digits = card_digits(card_number);
+    if digits.is_empty() {
+        return Some(Problem::Missing);
+    }
+    if !(12..=19).contains(&digits.len()) || !luhn_valid(&digits) {
+        return Some(Problem::Invalid);
+    }
+    None
+}
+
+// Split an MM/YY or MM/YYYY expiry date into its month and full year. Two-digit years are
+// taken to be in the 2000s.
+pub fn parse_expiry(expiry_date: &str) -> Option<(u32, u32)> {
+    let (month, year) = expiry_date.trim().split_once('/')?;
+    let (month, year) = (month.trim(), year.trim());
+    if month.len() != 2 || !(year.len() == 2 || year.len() == 4) {
+        return None;
+    }
+    let month: u32 = month.parse().ok()?;
+    let mut year: u32 = year.parse().ok()?;
+    if !(1..=12).contains(&month) {
+        return None;
+    }
+    if year < 100 {
+        year += 2000;
+    }
+    Some((month, year))
+}
+
+// Rewrite a parseable expiry in the configured year style; anything else is left as typed
+// so validation can report it
+pub fn normalize_expiry(expiry_date: &str, format: ExpiryFormat) -> String {
+    match parse_expiry(expiry_date) {
+        Some((month, year)) => match format {
+            ExpiryFormat::TwoDigitYear => format!("{:02}/{:02}", month, year % 100),
+            ExpiryFormat::FourDigitYear => format!("{:02}/{:04}", month, year),
+        },
+        None => expiry_date.to_string(),
+    }
+}
+
+pub fn check_expiry(expiry_date: &str) -> Option<Problem> {
+    if expiry_date.trim().is_empty() {
+        return Some(Problem::Missing);
+    }
+    let Some((month, year)) = parse_expiry(expiry_date) else {
+        return Some(Problem::Invalid);
+    };
+
+    // Compare whole months; a card is valid through the end of its expiry month
+    let today = chrono::Local::now().date_naive();
+    let now_index = today.year() as i64 * 12 + today.month0() as i64;
+    let expiry_index = year as i64 * 12 + (month - 1) as i64;
+    if expiry_index < now_index {
+        Some(Problem::Expired)
+    } else if expiry_index - now_index <= NEAR_EXPIRY_MONTHS as i64 {
+        Some(Problem::ExpiresSoon)
+    } else {
+        None
+    }
+}
+
+pub fn check_security_code(security_code: &str) -> Option<Problem> {
+    let code = security_code.trim();
+    if code.is_empty() {
+        return Some(Problem::Missing);
+    }
+    if !(3..=4).contains(&code.len()) || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Some(Problem::Invalid);
+    }
+    None
+}
+
+// A complete, valid card built from the first test number, expiring a couple of years out
+pub fn sample_test_card() -> CardInfo {
+    let expiry_year = (chrono::Local::now().year() + 2) % 100;
+    CardInfo {
+        card_number: TEST_CARD_NUMBERS[0].to_string(),
+        cardholder_name: String::new(),
+        expiry_date: format!("12/{:02}", expiry_year),
+        security_code: "123".to_string(),
+    }
+}
+
+// Only flag the network once the number itself passes, so a typo doesn't produce two messages
+fn check_network(card_number: &str) -> Option<Problem> {
+    if check_card_number(card_number).is_some() {
+        return None;
+    }
+    match detect_network(&card_digits(card_number)) {
+        Some(_) => None,
+        None => Some(Problem::UnknownNetwork),
+    }
+}
+
+// Why a number looks like example data rather than a real card, if it does
+pub fn placeholder_reason(card_number: &str) -> Option<&'static str> {
+    let number = card_digits(card_number);
+    if TEST_CARD_NUMBERS.contains(&number.as_str()) {
+        return Some(TEST_NUMBER_TEXT);
+    }
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.is_empty() {
+        return None;
+    }
+    if digits.iter().all(|d| *d == digits[0]) {
+        return Some(REPEATED_DIGITS_TEXT);
+    }
+    // 1234567890123... or 9876543210987..., wrapping around through zero
+    let steps_by = |step: u32| digits.windows(2).all(|pair| pair[1] == (pair[0] + step) % 10);
+    if steps_by(1) || steps_by(9) {
+        return Some(SEQUENTIAL_DIGITS_TEXT);
+    }
+    None
+}
+
+// Same rule as the network check: only once the number itself is otherwise acceptable
+fn check_placeholder(card_number: &str) -> Option<Problem> {
+    if check_card_number(card_number).is_some() {
+        return None;
+    }
+    placeholder_reason(card_number).map(Problem::Placeholder)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ValidationResult {
+    pub issues: Vec<FieldIssue>,
+}
+
+impl ValidationResult {
+    pub fn errors(&self) -> impl Iterator<Item = &FieldIssue> {
+        self.issues.iter().filter(|issue| !issue.problem.overridable())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &FieldIssue> {
+        self.issues.iter().filter(|issue| issue.problem.overridable())
+    }
+
+    // True when at least one problem can't be bypassed
+    pub fn is_blocked(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn has_warnings(&self) -> bool {
+        self.warnings().next().is_some()
+    }
+
+    // Every field in form order with all of its problems, for the "Validate" breakdown
+    pub fn by_field(&self) -> Vec<(Field, Vec<Problem>)> {
+        Field::ALL
+            .into_iter()
+            .map(|field| (field, self.issues.iter().filter(|issue| issue.field == field).map(|issue| issue.problem).collect()))
+            .collect()
+    }
+
+    // The blocking problem with one field, e.g. for its inline outline
+    pub fn field_error(&self, field: Field) -> Option<Problem> {
+        self.errors().find(|issue| issue.field == field).map(|issue| issue.problem)
+    }
+
+    // Build a status line such as "Missing: expiry, CVV. Invalid: card number"
+    pub fn error_summary(&self) -> String {
+        let list = |problem: Problem| -> Vec<&'static str> {
+            self.issues
+                .iter()
+                .filter(|issue| issue.problem == problem)
+                .map(|issue| issue.field.short_name())
+                .collect()
+        };
+
+        let mut parts = Vec::new();
+        for (problem, prefix) in [
+            (Problem::Missing, MISSING_PREFIX),
+            (Problem::Invalid, INVALID_PREFIX),
+            (Problem::Expired, EXPIRED_PREFIX),
+            (Problem::PatternMismatch, PATTERN_MISMATCH_PREFIX),
+        ] {
+            let fields = list(problem);
+            if !fields.is_empty() {
+                parts.push(format!("{}: {}", prefix, fields.join(", ")));
+            }
+        }
+        parts.join(". ")
+    }
+
+    pub fn warning_summary(&self) -> String {
+        let texts: Vec<&'static str> = self
+            .warnings()
+            .filter_map(|issue| match issue.problem {
+                Problem::ExpiresSoon => Some(EXPIRES_SOON_TEXT),
+                Problem::UnknownNetwork => Some(UNKNOWN_NETWORK_TEXT),
+                Problem::Placeholder(reason) => Some(reason),
+                _ => None,
+            })
+            .collect();
+        texts.join(". ")
+    }
+}
+
+// Run every field check and collect the problems in form order
+pub fn validate(card_info: &CardInfo) -> ValidationResult {
+    let checks = [
+        (Field::CardNumber, check_card_number(&card_info.card_number)),
+        (Field::CardNumber, check_network(&card_info.card_number)),
+        (Field::CardNumber, check_placeholder(&card_info.card_number)),
+        (Field::ExpiryDate, check_expiry(&card_info.expiry_date)),
+        (Field::SecurityCode, check_security_code(&card_info.security_code)),
+    ];
+
+    let issues = checks
+        .into_iter()
+        .filter_map(|(field, problem)| problem.map(|problem| FieldIssue { field, problem }))
+        .collect();
+    ValidationResult { issues }
+}
@@ -0,0 +1,109 @@
+// This module contains the WebSocket transport for streaming backends: the card goes out as one
+// JSON text message and the server answers with status messages until a terminal one.
+//
+// Terminal messages are JSON objects whose "status" is one of SUCCESS_STATUSES or
+// FAILURE_STATUSES; anything else is treated as progress and passed to `on_status`.
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::network::{self, SendError, SendSuccess};
+use crate::CardInfo;
+
+const CONNECT_ATTEMPTS: u32 = 3;
+const RECONNECT_DELAY: Duration = Duration::from_millis(500); // Doubled after each failed attempt
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30); // Longest wait for the next frame
+
+const SUCCESS_STATUSES: &[&str] = &["success", "ok", "accepted", "done"];
+const FAILURE_STATUSES: &[&str] = &["error", "failed", "rejected", "declined"];
+
+type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+// Only the handshake is retried. Once the card has been sent a dropped connection is reported
+// rather than retried, so a backend never receives the same card twice.
+async fn connect(url: &str) -> Result<Socket, String> {
+    let mut delay = RECONNECT_DELAY;
+    let mut last_error = String::new();
+    for attempt in 1..=CONNECT_ATTEMPTS {
+        match tokio_tungstenite::connect_async(url).await {
+            Ok((socket, _)) => return Ok(socket),
+            Err(e) => {
+                last_error = e.to_string();
+                tracing::warn!(attempt, "WebSocket connect attempt failed: {}", last_error);
+            }
+        }
+        if attempt < CONNECT_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    Err(format!("Couldn't connect after {} attempts: {}", CONNECT_ATTEMPTS, last_error))
+}
+
+pub async fn send_card_info(
+    url: &str,
+    card_info: &CardInfo,
+    on_status: &(dyn Fn(String) + Send + Sync),
+) -> Result<SendSuccess, SendError> {
+    if url.is_empty() {
+        return Err("No WebSocket URL configured".to_string().into());
+    }
+    let mut socket = connect(url).await.map_err(SendError::unreachable)?;
+
+    let payload = serde_json::to_string(card_info).map_err(|e| format!("Couldn't encode card info: {}", e))?;
+    socket
+        .send(Message::text(payload))
+        .await
+        .map_err(|e| format!("Failed to send card info: {}", e))?;
+
+    loop {
+        let frame = match tokio::time::timeout(RESPONSE_TIMEOUT, socket.next()).await {
+            Err(_) => return Err(SendError::timeout(format!("No response within {} s", RESPONSE_TIMEOUT.as_secs()))),
+            Ok(None) => return Err("Connection closed before a final status".to_string().into()),
+            Ok(Some(Err(e))) => return Err(format!("Connection lost: {}", e).into()),
+            Ok(Some(Ok(frame))) => frame,
+        };
+
+        match frame {
+            Message::Text(text) => {
+                if let Some(result) = terminal_result(text.as_str()) {
+                    let _ = socket.close(None).await;
+                    return result.map_err(SendError::from);
+                }
+                on_status(status_text(text.as_str()));
+            }
+            Message::Close(close) => {
+                let reason = close.map(|close| close.reason.to_string()).unwrap_or_default();
+                return Err(if reason.is_empty() {
+                    "Server closed the connection before a final status".to_string().into()
+                } else {
+                    format!("Server closed the connection: {}", reason).into()
+                });
+            }
+            _ => {} // Pings are answered by tungstenite; binary frames aren't part of the protocol
+        }
+    }
+}
+
+fn terminal_result(text: &str) -> Option<Result<SendSuccess, String>> {
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    let status = json.get("status")?.as_str()?.to_ascii_lowercase();
+    if SUCCESS_STATUSES.contains(&status.as_str()) {
+        Some(Ok(SendSuccess { transaction_id: network::extract_transaction_id(text.as_bytes()), status: None, connection: None, headers: Vec::new() }))
+    } else if FAILURE_STATUSES.contains(&status.as_str()) {
+        Some(Err(format!("Server rejected the card: {}", status_text(text))))
+    } else {
+        None
+    }
+}
+
+// Prefer a human-readable "message" field, falling back to the status or the raw frame
+fn status_text(text: &str) -> String {
+    let json: Option<serde_json::Value> = serde_json::from_str(text).ok();
+    json.as_ref()
+        .and_then(|json| json.get("message").or_else(|| json.get("status")))
+        .and_then(|value| value.as_str())
+        .map_or_else(|| text.to_string(), str::to_owned)
+}